@@ -0,0 +1,88 @@
+//! Fuzz a token compiled to WASM, without writing any Rust.
+//!
+//! ```text
+//! cargo fuzz run --fuzz-dir fuzz-wasm soroban-token-fuzz -- \
+//!     -wasm=my_token.wasm -token=my_token.json [libFuzzer flags]
+//! ```
+//!
+//! The token file is JSON that describes how to initialize the token,
+//! and optionally how to call its mint method
+//! and how many addresses to start with:
+//!
+//! ```json
+//! {
+//!   "init": {
+//!     "fn_name": "initialize",
+//!     "args": ["admin", { "u32": 7 }, { "string": "token" }, { "string": "TKN" }]
+//!   },
+//!   "mint": { "fn_name": "mint", "args": ["to", "amount"] },
+//!   "addresses": 5
+//! }
+//! ```
+//!
+//! The `-wasm` and `-token` flags are read by this program;
+//! libFuzzer ignores them with a warning, and interprets
+//! all the other arguments as usual.
+
+#![no_main]
+
+use libfuzzer_sys::{fuzz_target, Corpus};
+use serde::Deserialize;
+use soroban_token_fuzzer::*;
+use std::sync::{Arc, OnceLock};
+
+#[derive(Deserialize)]
+struct TokenFile {
+    init: FnSpec,
+    mint: Option<FnSpec>,
+    addresses: Option<usize>,
+}
+
+struct Token {
+    /// Shared with every run's `Config` instead of copied.
+    wasm: Arc<[u8]>,
+    file: TokenFile,
+}
+
+fuzz_target!(|input: Input| -> Corpus {
+    let token = token();
+
+    let mut config = Config::wasm(token.wasm.clone(), token.file.init.clone());
+    if let Some(mint) = &token.file.mint {
+        config = config.with_mint(mint.clone());
+    }
+    if let Some(addresses) = token.file.addresses {
+        config = config.with_number_of_addresses(addresses);
+    }
+
+    fuzz_token(config, input)
+});
+
+/// Load the token from the files named on the command line.
+fn token() -> &'static Token {
+    static TOKEN: OnceLock<Token> = OnceLock::new();
+
+    TOKEN.get_or_init(|| {
+        let arg = |name: &str| {
+            let prefix = format!("-{name}=");
+            std::env::args()
+                .find_map(|arg| arg.strip_prefix(&prefix).map(str::to_string))
+                .unwrap_or_else(|| panic!("missing argument `{prefix}<path>`"))
+        };
+
+        let wasm_path = arg("wasm");
+        let token_path = arg("token");
+
+        let wasm =
+            std::fs::read(&wasm_path).unwrap_or_else(|e| panic!("reading `{wasm_path}`: {e}"));
+        let file = std::fs::read_to_string(&token_path)
+            .unwrap_or_else(|e| panic!("reading `{token_path}`: {e}"));
+        let file =
+            serde_json::from_str(&file).unwrap_or_else(|e| panic!("parsing `{token_path}`: {e}"));
+
+        Token {
+            wasm: wasm.into(),
+            file,
+        }
+    })
+}
@@ -7,3 +7,6 @@ fuzz_target!(|input: Input| -> Corpus {
     let config = Config::native();
     fuzz_token(config, input)
 });
+
+// Mutate decoded inputs instead of raw bytes.
+soroban_token_fuzzer::token_fuzz_mutator!();
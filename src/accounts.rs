@@ -0,0 +1,512 @@
+//! How the fuzzer's addresses authorize calls:
+//! the signers of classic accounts,
+//! and custom account contracts for contract addresses.
+//!
+//! Except for `MockAuthContract`, the account contracts implement `ContractFunctionSet`
+//! directly instead of using `#[contractimpl]`, like `ProxyContract`,
+//! so that rejecting an authorization unwinds into the host
+//! and fails the call the same way a WASM account would.
+
+use crate::util::TOKEN_FN_NAMES;
+use ed25519_dalek::{Signer, SigningKey};
+use serde::{Deserialize, Serialize};
+use soroban_sdk::auth::Context;
+use soroban_sdk::testutils::arbitrary::arbitrary;
+use soroban_sdk::testutils::ContractFunctionSet;
+use soroban_sdk::xdr::{ScAddress, ScVal, SorobanAuthorizedFunction, SorobanAuthorizedInvocation};
+use soroban_sdk::{
+    contract, contractimpl, contracttype, Address, BytesN, Env, Error, IntoVal, Symbol, TryFromVal,
+    Val,
+};
+use std::vec::Vec as RustVec;
+
+/// A classic account's signers, and which of them sign.
+#[derive(Clone)]
+pub struct ClassicAccount {
+    /// The first signer is the master key,
+    /// whose weight is the account's master weight.
+    pub signers: RustVec<ClassicSigner>,
+    pub medium_threshold: u8,
+}
+
+#[derive(Clone)]
+pub struct ClassicSigner {
+    pub key: SigningKey,
+    /// Signers with zero weight don't belong to the account.
+    pub weight: u8,
+    pub signing: bool,
+}
+
+impl ClassicAccount {
+    /// An account signed by its master key alone.
+    pub fn single(key: SigningKey) -> ClassicAccount {
+        ClassicAccount {
+            signers: vec![ClassicSigner {
+                key,
+                weight: 1,
+                signing: true,
+            }],
+            medium_threshold: 0,
+        }
+    }
+
+    /// The signature for the payload of an authorization entry.
+    ///
+    /// The host requires the signatures to be ordered by public key.
+    pub fn sign(&self, env: &Env, payload: &[u8; 32]) -> ScVal {
+        let mut keys: RustVec<_> = self
+            .signers
+            .iter()
+            .filter(|signer| signer.signing)
+            .map(|signer| &signer.key)
+            .collect();
+        keys.sort_by_key(|key| key.verifying_key().to_bytes());
+
+        let mut signatures = soroban_sdk::Vec::new(env);
+        for key in keys {
+            signatures.push_back(sign_payload_for_account(env, key, payload));
+        }
+        signatures.try_into().unwrap()
+    }
+
+    /// Whether the host rejects the signature.
+    pub fn rejects(&self) -> bool {
+        let signing: RustVec<_> = self
+            .signers
+            .iter()
+            .filter(|signer| signer.signing)
+            .map(|signer| signer.key.verifying_key().to_bytes())
+            .collect();
+        self.rejects_signers(&signing)
+    }
+
+    /// Whether the host rejects valid signatures from these distinct keys:
+    /// when there are none, when one doesn't belong to the account,
+    /// or when their weight is below the medium threshold.
+    pub fn rejects_signers(&self, public_keys: &[[u8; 32]]) -> bool {
+        let weights: RustVec<u32> = public_keys
+            .iter()
+            .map(|public_key| {
+                self.signers
+                    .iter()
+                    .find(|signer| signer.key.verifying_key().to_bytes() == *public_key)
+                    .map_or(0, |signer| signer.weight as u32)
+            })
+            .collect();
+
+        weights.is_empty()
+            || weights.contains(&0)
+            || weights.iter().sum::<u32>() < self.medium_threshold as u32
+    }
+}
+
+/// The contract behind a contract address, and how to sign for it.
+#[derive(Clone)]
+pub enum AccountContract {
+    /// Accepts any authorization.
+    AcceptAll,
+    /// Requires an ed25519 signature from its key.
+    Ed25519(SigningKey),
+    /// Requires ed25519 signatures from 2 of its 3 keys.
+    ///
+    /// The fuzzer signs with the keys whose flags in `signing` are set.
+    Multisig {
+        keys: Box<[SigningKey; 3]>,
+        signing: [bool; 3],
+    },
+    /// Accepts any authorization, except for calls to `denied_fn_name`,
+    /// or with an `i128` argument above `amount_limit`.
+    Policy {
+        denied_fn_name: &'static str,
+        amount_limit: i128,
+    },
+    /// Calls back into the token from `__check_auth`,
+    /// and rejects the authorization if that call fails
+    /// and `propagate_error` is set.
+    Reentrant {
+        call: ReentrantCall,
+        propagate_error: bool,
+    },
+}
+
+/// The token call a `Reentrant` account makes from `__check_auth`,
+/// against the first other address in the call being authorized.
+///
+/// The account doesn't call the token on its own behalf,
+/// which the host authorizes when the account invokes the token directly.
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
+pub enum ReentrantCall {
+    /// Transfer from the victim to the account.
+    Transfer(i128),
+    /// Approve the account to spend the victim's tokens.
+    Approve(i128),
+    /// Read the victim's balance.
+    Balance,
+}
+
+impl ReentrantCall {
+    /// The number of variants, for encoding artifacts.
+    pub(crate) const VARIANTS: u64 = 3;
+}
+
+impl AccountContract {
+    pub fn multisig(keys: [SigningKey; 3], signing: [bool; 3]) -> AccountContract {
+        AccountContract::Multisig {
+            keys: Box::new(keys),
+            signing,
+        }
+    }
+
+    pub fn policy(denied_fn: u8, amount_limit: i128) -> AccountContract {
+        AccountContract::Policy {
+            denied_fn_name: TOKEN_FN_NAMES[denied_fn as usize % TOKEN_FN_NAMES.len()],
+            amount_limit,
+        }
+    }
+
+    /// Register the contract at `address`.
+    ///
+    /// Contract addresses need to have registered contracts to be authorizers,
+    /// at least according to the sdk's mock_auths method.
+    pub fn register(&self, env: &Env, address: &Address) {
+        match self {
+            AccountContract::AcceptAll => {
+                env.register_contract(address, MockAuthContract);
+            }
+            AccountContract::Ed25519(key) => {
+                env.register_contract(
+                    address,
+                    Ed25519Account {
+                        public_key: key.verifying_key().to_bytes(),
+                    },
+                );
+            }
+            AccountContract::Multisig { keys, .. } => {
+                env.register_contract(
+                    address,
+                    MultisigAccount {
+                        public_keys: keys.each_ref().map(|key| key.verifying_key().to_bytes()),
+                    },
+                );
+            }
+            AccountContract::Policy {
+                denied_fn_name,
+                amount_limit,
+            } => {
+                env.register_contract(
+                    address,
+                    PolicyAccount {
+                        denied_fn_name,
+                        amount_limit: *amount_limit,
+                    },
+                );
+            }
+            AccountContract::Reentrant {
+                call,
+                propagate_error,
+            } => {
+                env.register_contract(
+                    address,
+                    ReentrantAccount {
+                        call: call.clone(),
+                        propagate_error: *propagate_error,
+                    },
+                );
+            }
+        }
+    }
+
+    /// The signature for the payload of an authorization entry.
+    pub fn sign(&self, env: &Env, payload: &[u8; 32]) -> ScVal {
+        match self {
+            AccountContract::AcceptAll
+            | AccountContract::Policy { .. }
+            | AccountContract::Reentrant { .. } => ScVal::Void,
+            AccountContract::Ed25519(key) => {
+                let signature =
+                    BytesN::<64>::try_from_val(env, &key.sign(payload).to_bytes()).unwrap();
+                signature.try_into().unwrap()
+            }
+            AccountContract::Multisig { keys, signing } => {
+                let mut signatures = soroban_sdk::Vec::new(env);
+                for (key, _) in keys.iter().zip(signing).filter(|(_, signing)| **signing) {
+                    signatures.push_back(sign_payload_for_account(env, key, payload));
+                }
+                signatures.try_into().unwrap()
+            }
+        }
+    }
+
+    /// Whether the account rejects valid signatures from these distinct keys,
+    /// for accounts that take a vector of signatures.
+    pub fn rejects_signers(&self, public_keys: &[[u8; 32]]) -> bool {
+        match self {
+            AccountContract::Multisig { keys, .. } => {
+                let known = |public_key: &[u8; 32]| {
+                    keys.iter()
+                        .any(|key| key.verifying_key().to_bytes() == *public_key)
+                };
+                public_keys.len() < 2 || !public_keys.iter().all(known)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the account at `address` rejects authorizing `invocation`,
+    /// even though it is correctly signed.
+    pub fn rejects(&self, address: &Address, invocation: &SorobanAuthorizedInvocation) -> bool {
+        match self {
+            AccountContract::AcceptAll | AccountContract::Ed25519(_) => false,
+            AccountContract::Multisig { signing, .. } => {
+                signing.iter().filter(|signing| **signing).count() < 2
+            }
+            AccountContract::Policy {
+                denied_fn_name,
+                amount_limit,
+            } => {
+                let mut contexts = vec![invocation];
+                while let Some(invocation) = contexts.pop() {
+                    let SorobanAuthorizedFunction::ContractFn(function) = &invocation.function
+                    else {
+                        panic!("the fuzzer only authorizes contract calls")
+                    };
+
+                    if function.function_name.0.as_slice() == denied_fn_name.as_bytes() {
+                        return true;
+                    }
+
+                    let above_limit = function.args.iter().any(|arg| match arg {
+                        ScVal::I128(parts) => {
+                            let amount = ((parts.hi as i128) << 64) | parts.lo as i128;
+                            amount > *amount_limit
+                        }
+                        _ => false,
+                    });
+                    if above_limit {
+                        return true;
+                    }
+
+                    contexts.extend(invocation.sub_invocations.iter());
+                }
+
+                false
+            }
+            AccountContract::Reentrant {
+                call,
+                propagate_error,
+            } => {
+                // The host doesn't allow reentering the token,
+                // which is on the call stack unless the account
+                // is authorizing a proxy call to it.
+                let (token_invocation, reentrant) = match invocation.sub_invocations.first() {
+                    Some(token_invocation) => (token_invocation, false),
+                    None => (invocation, true),
+                };
+                let SorobanAuthorizedFunction::ContractFn(function) = &token_invocation.function
+                else {
+                    panic!("the fuzzer only authorizes contract calls")
+                };
+
+                let address = ScAddress::try_from(address.clone()).unwrap();
+                let has_victim = function
+                    .args
+                    .iter()
+                    .any(|arg| matches!(arg, ScVal::Address(arg) if *arg != address));
+
+                // Nor does it let the account spend the victim's tokens
+                // without the victim's authorization.
+                let fails = reentrant || !matches!(call, ReentrantCall::Balance);
+                *propagate_error && has_victim && fails
+            }
+        }
+    }
+}
+
+/// An account contract that accepts any authorization.
+#[contract]
+#[derive(Clone)]
+pub struct MockAuthContract;
+
+#[contractimpl]
+impl MockAuthContract {
+    #[allow(non_snake_case)]
+    pub fn __check_auth(_signature_payload: Val, _signatures: Val, _auth_context: Val) {}
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub(crate) struct AccountEd25519Signature {
+    pub(crate) public_key: BytesN<32>,
+    pub(crate) signature: BytesN<64>,
+}
+
+pub(crate) fn sign_payload_for_account(
+    env: &Env,
+    signer: &SigningKey,
+    payload: &[u8],
+) -> AccountEd25519Signature {
+    AccountEd25519Signature {
+        public_key: BytesN::<32>::try_from_val(env, &signer.verifying_key().to_bytes()).unwrap(),
+        signature: BytesN::<64>::try_from_val(env, &signer.sign(payload).to_bytes()).unwrap(),
+    }
+}
+
+/// The arguments to `__check_auth`.
+struct CheckAuthArgs {
+    signature_payload: BytesN<32>,
+    signature: Val,
+    auth_contexts: soroban_sdk::Vec<Context>,
+}
+
+impl CheckAuthArgs {
+    fn parse(env: &Env, func: &str, args: &[Val]) -> Option<CheckAuthArgs> {
+        if func != "__check_auth" {
+            return None;
+        }
+
+        let [signature_payload, signature, auth_contexts] = args else {
+            panic!("wrong number of arguments to __check_auth");
+        };
+
+        Some(CheckAuthArgs {
+            signature_payload: BytesN::try_from_val(env, signature_payload).unwrap(),
+            signature: *signature,
+            auth_contexts: soroban_sdk::Vec::try_from_val(env, auth_contexts).unwrap(),
+        })
+    }
+}
+
+/// An account contract that requires an ed25519 signature from its key.
+#[derive(Clone)]
+pub struct Ed25519Account {
+    pub public_key: [u8; 32],
+}
+
+impl ContractFunctionSet for Ed25519Account {
+    fn call(&self, func: &str, env: Env, args: &[Val]) -> Option<Val> {
+        let args = CheckAuthArgs::parse(&env, func, args)?;
+
+        let public_key = BytesN::<32>::try_from_val(&env, &self.public_key).unwrap();
+        let signature = BytesN::<64>::try_from_val(&env, &args.signature).unwrap();
+        env.crypto()
+            .ed25519_verify(&public_key, &args.signature_payload.into(), &signature);
+
+        Some(().into_val(&env))
+    }
+}
+
+/// An account contract that requires ed25519 signatures from 2 of its 3 keys.
+#[derive(Clone)]
+pub struct MultisigAccount {
+    pub public_keys: [[u8; 32]; 3],
+}
+
+impl ContractFunctionSet for MultisigAccount {
+    fn call(&self, func: &str, env: Env, args: &[Val]) -> Option<Val> {
+        let args = CheckAuthArgs::parse(&env, func, args)?;
+
+        let signatures =
+            soroban_sdk::Vec::<AccountEd25519Signature>::try_from_val(&env, &args.signature)
+                .unwrap();
+
+        let mut signed = RustVec::new();
+        for signature in signatures.iter() {
+            let public_key = signature.public_key.to_array();
+            assert!(self.public_keys.contains(&public_key), "unknown signer");
+            assert!(!signed.contains(&public_key), "duplicate signer");
+
+            env.crypto().ed25519_verify(
+                &signature.public_key,
+                &args.signature_payload.clone().into(),
+                &signature.signature,
+            );
+            signed.push(public_key);
+        }
+
+        assert!(signed.len() >= 2, "not enough signatures");
+
+        Some(().into_val(&env))
+    }
+}
+
+/// An account contract that rejects authorizing calls to `denied_fn_name`,
+/// or with an `i128` argument above `amount_limit`.
+#[derive(Clone)]
+pub struct PolicyAccount {
+    pub denied_fn_name: &'static str,
+    pub amount_limit: i128,
+}
+
+impl ContractFunctionSet for PolicyAccount {
+    fn call(&self, func: &str, env: Env, args: &[Val]) -> Option<Val> {
+        let args = CheckAuthArgs::parse(&env, func, args)?;
+
+        for context in args.auth_contexts.iter() {
+            let Context::Contract(context) = context else {
+                panic!("unexpected auth context");
+            };
+
+            assert!(
+                context.fn_name != soroban_sdk::Symbol::new(&env, self.denied_fn_name),
+                "denied function"
+            );
+
+            for arg in context.args.iter() {
+                if let Ok(amount) = i128::try_from_val(&env, &arg) {
+                    assert!(amount <= self.amount_limit, "amount above limit");
+                }
+            }
+        }
+
+        Some(().into_val(&env))
+    }
+}
+
+/// An account contract that calls back into the token from `__check_auth`,
+/// and rejects the authorization if that call fails and `propagate_error` is set.
+#[derive(Clone)]
+pub struct ReentrantAccount {
+    pub call: ReentrantCall,
+    pub propagate_error: bool,
+}
+
+impl ContractFunctionSet for ReentrantAccount {
+    fn call(&self, func: &str, env: Env, args: &[Val]) -> Option<Val> {
+        let args = CheckAuthArgs::parse(&env, func, args)?;
+
+        // The token is the contract of the innermost call being authorized.
+        let Some(Context::Contract(context)) = args.auth_contexts.last() else {
+            panic!("unexpected auth context");
+        };
+        let token = context.contract;
+        let account = env.current_contract_address();
+
+        let victim = context
+            .args
+            .iter()
+            .filter_map(|arg| Address::try_from_val(&env, &arg).ok())
+            .find(|arg| *arg != account);
+        let Some(victim) = victim else {
+            return Some(().into_val(&env));
+        };
+
+        let (fn_name, call_args) = match &self.call {
+            ReentrantCall::Transfer(amount) => {
+                ("transfer", (victim, account, *amount).into_val(&env))
+            }
+            ReentrantCall::Approve(amount) => (
+                "approve",
+                (victim, account, *amount, env.ledger().sequence()).into_val(&env),
+            ),
+            ReentrantCall::Balance => ("balance", (victim,).into_val(&env)),
+        };
+        let r =
+            env.try_invoke_contract::<Val, Error>(&token, &Symbol::new(&env, fn_name), call_args);
+
+        if self.propagate_error {
+            assert!(matches!(r, Ok(Ok(_))), "reentrant call failed");
+        }
+
+        Some(().into_val(&env))
+    }
+}
@@ -1,115 +1,308 @@
-use crate::input::NUMBER_OF_ADDRESSES;
+use crate::accounts::{AccountContract, ClassicAccount, ClassicSigner, ReentrantCall};
+use crate::input::MAX_NUMBER_OF_ADDRESSES;
 use arbitrary::Unstructured;
 use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
 use soroban_sdk::testutils::arbitrary::arbitrary;
 use soroban_sdk::xdr::{
     AccountEntry, AccountEntryExt, AccountId, AlphaNum4, AssetCode4, Hash, LedgerEntry,
     LedgerEntryData, LedgerEntryExt, LedgerKey, LedgerKeyAccount, LedgerKeyTrustLine, PublicKey,
-    ScAddress, SequenceNumber, Signer, SignerKey, Thresholds, TrustLineAsset, TrustLineEntry,
-    TrustLineEntryExt, TrustLineFlags, Uint256,
+    ScAddress, ScVal, SequenceNumber, Signer, SignerKey, SorobanAuthorizedInvocation, Thresholds,
+    TrustLineAsset, TrustLineEntry, TrustLineEntryExt, TrustLineFlags, Uint256,
 };
 use soroban_sdk::{Address, Env, TryFromVal};
 use std::rc::Rc;
 use std::vec::Vec as RustVec;
 
-#[derive(Clone, Debug, arbitrary::Arbitrary)]
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
 pub struct AddressGenerator {
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(u64::MIN..=u64::MAX - NUMBER_OF_ADDRESSES as u64))]
+    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(u64::MIN..=u64::MAX - MAX_NUMBER_OF_ADDRESSES as u64))]
     pub address_seed: u64,
-    pub address_types: [AddressType; NUMBER_OF_ADDRESSES],
+    /// The types of the initial addresses,
+    /// repeated if there are more addresses than types.
+    pub address_types: RustVec<AddressType>,
 }
 
-#[derive(Clone, Debug, arbitrary::Arbitrary)]
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
 pub enum AddressType {
+    /// A classic account signed by its master key.
     Account,
+    /// A classic account with two more signers,
+    /// fuzzed weights and a fuzzed medium threshold.
+    MultisigAccount {
+        master: AccountSignerType,
+        signers: [AccountSignerType; 2],
+        medium_threshold: u8,
+    },
+    /// A contract that accepts any authorization.
     Contract,
+    /// A contract that requires an ed25519 signature.
+    Ed25519Contract,
+    /// A 2-of-3 multisig contract,
+    /// signed by the keys whose flags are set.
+    MultisigContract { signing: [bool; 3] },
+    /// A contract that rejects authorizing calls to one token function,
+    /// or with amounts above a limit.
+    PolicyContract { denied_fn: u8, amount_limit: i128 },
+    /// A contract that calls back into the token from `__check_auth`.
+    ReentrantContract {
+        call: ReentrantCall,
+        propagate_error: bool,
+    },
+}
+
+impl AddressType {
+    /// The number of variants, for encoding artifacts.
+    pub(crate) const VARIANTS: u64 = 7;
+}
+
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
+pub struct AccountSignerType {
+    pub weight: u8,
+    pub signing: bool,
 }
 
 pub struct TestSigner {
     pub address: Address,
+    /// The master key of a classic account.
     pub key: Option<SigningKey>,
+    /// The signers of a classic account.
+    pub classic_account: Option<ClassicAccount>,
+    /// The account contract of a contract address.
+    pub account_contract: Option<AccountContract>,
+}
+
+impl TestSigner {
+    /// The signature for the payload of an authorization entry.
+    pub(crate) fn sign(&self, env: &Env, payload: &[u8; 32]) -> ScVal {
+        match (&self.classic_account, &self.account_contract) {
+            (Some(classic_account), _) => classic_account.sign(env, payload),
+            (None, Some(account_contract)) => account_contract.sign(env, payload),
+            (None, None) => ScVal::Void,
+        }
+    }
+
+    /// Whether the account rejects valid signatures from these distinct keys.
+    pub(crate) fn rejects_signers(&self, public_keys: &[[u8; 32]]) -> bool {
+        match (&self.classic_account, &self.account_contract) {
+            (Some(classic_account), _) => classic_account.rejects_signers(public_keys),
+            (None, Some(account_contract)) => account_contract.rejects_signers(public_keys),
+            (None, None) => false,
+        }
+    }
+
+    /// Whether the account rejects authorizing `invocation`,
+    /// even though the entry is signed for it.
+    pub(crate) fn rejects(&self, invocation: &SorobanAuthorizedInvocation) -> bool {
+        self.classic_account
+            .as_ref()
+            .is_some_and(|classic_account| classic_account.rejects())
+            || self
+                .account_contract
+                .as_ref()
+                .is_some_and(|account_contract| account_contract.rejects(&self.address, invocation))
+    }
 }
 
 impl AddressGenerator {
-    pub fn generate_signers(&self, env: &Env) -> RustVec<TestSigner> {
-        self.generate_signers_with_bytes(env)
-            .into_iter()
-            .map(|(a, _)| a)
+    /// The types of the first `number_of_addresses` addresses.
+    pub fn initial_address_types(&self, number_of_addresses: usize) -> RustVec<AddressType> {
+        if self.address_types.is_empty() {
+            return vec![AddressType::Account; number_of_addresses];
+        }
+
+        self.address_types
+            .iter()
+            .cycle()
+            .take(number_of_addresses)
+            .cloned()
+            .collect()
+    }
+
+    pub fn generate_signers(
+        &self,
+        env: &Env,
+        address_types: &[AddressType],
+    ) -> RustVec<TestSigner> {
+        address_types
+            .iter()
+            .enumerate()
+            .map(|(i, address_type)| self.generate_signer(env, i, address_type))
             .collect()
     }
 
-    fn generate_signers_with_bytes(&self, env: &Env) -> RustVec<(TestSigner, [u8; 32])> {
-        let mut signers = RustVec::<(TestSigner, [u8; 32])>::new();
+    /// Generate the signer of the `index`th address.
+    pub fn generate_signer(
+        &self,
+        env: &Env,
+        index: usize,
+        address_type: &AddressType,
+    ) -> TestSigner {
+        assert!(index < MAX_NUMBER_OF_ADDRESSES);
 
         // fixme seed of 0 or 1 seems to generate bogus contract addresses
-        for i in 0..NUMBER_OF_ADDRESSES {
-            let seed = self
-                .address_seed
-                .checked_add(i as u64)
-                .expect("Overflow")
-                .to_be_bytes();
-            let signer_bytes: [u8; 32] = [
-                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, seed[0],
-                seed[1], seed[2], seed[3], seed[4], seed[5], seed[6], seed[7],
-            ];
-
-            let test_signer = match self.address_types[i] {
-                AddressType::Account => {
-                    let signing_key = SigningKey::from_bytes(&signer_bytes);
-                    let verifying_key = signing_key.verifying_key().to_bytes();
-
-                    let account_id =
-                        AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(verifying_key)));
-                    let sc_address = ScAddress::Account(account_id);
-                    let address = Address::try_from_val(env, &sc_address).unwrap();
-                    let test_signer = TestSigner {
-                        address,
-                        key: Some(signing_key),
-                    };
-
-                    test_signer
-                }
-                AddressType::Contract => {
-                    let address =
-                        Address::try_from_val(env, &ScAddress::Contract(Hash(signer_bytes)))
-                            .unwrap();
-                    let test_signer = TestSigner { address, key: None };
+        let seed = self
+            .address_seed
+            .checked_add(index as u64)
+            .expect("Overflow")
+            .to_be_bytes();
+        let signer_bytes: [u8; 32] = [
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, seed[0],
+            seed[1], seed[2], seed[3], seed[4], seed[5], seed[6], seed[7],
+        ];
 
-                    test_signer
+        match address_type {
+            AddressType::Account => account_signer(
+                env,
+                ClassicAccount::single(SigningKey::from_bytes(&signer_bytes)),
+            ),
+            AddressType::MultisigAccount {
+                master,
+                signers,
+                medium_threshold,
+            } => {
+                let mut classic_signers = vec![ClassicSigner {
+                    key: SigningKey::from_bytes(&signer_bytes),
+                    weight: master.weight,
+                    signing: master.signing,
+                }];
+                for (i, signer) in (1..).zip(signers) {
+                    let mut key_bytes = signer_bytes;
+                    key_bytes[0] = i;
+                    classic_signers.push(ClassicSigner {
+                        key: SigningKey::from_bytes(&key_bytes),
+                        weight: signer.weight,
+                        signing: signer.signing,
+                    });
                 }
-            };
+                account_signer(
+                    env,
+                    ClassicAccount {
+                        signers: classic_signers,
+                        medium_threshold: *medium_threshold,
+                    },
+                )
+            }
+            AddressType::Contract => contract_signer(env, signer_bytes, AccountContract::AcceptAll),
+            AddressType::Ed25519Contract => contract_signer(
+                env,
+                signer_bytes,
+                AccountContract::Ed25519(SigningKey::from_bytes(&signer_bytes)),
+            ),
+            AddressType::MultisigContract { signing } => {
+                let keys = [1, 2, 3].map(|i| {
+                    let mut key_bytes = signer_bytes;
+                    key_bytes[0] = i;
+                    SigningKey::from_bytes(&key_bytes)
+                });
+                contract_signer(env, signer_bytes, AccountContract::multisig(keys, *signing))
+            }
+            AddressType::PolicyContract {
+                denied_fn,
+                amount_limit,
+            } => contract_signer(
+                env,
+                signer_bytes,
+                AccountContract::policy(*denied_fn, *amount_limit),
+            ),
+            AddressType::ReentrantContract {
+                call,
+                propagate_error,
+            } => contract_signer(
+                env,
+                signer_bytes,
+                AccountContract::Reentrant {
+                    call: call.clone(),
+                    propagate_error: *propagate_error,
+                },
+            ),
+        }
+    }
+}
 
-            signers.push((test_signer, signer_bytes));
+impl TestSigner {
+    /// Create the ledger entries of a classic account,
+    /// with a trustline to the native token.
+    pub fn setup_account_storage(&self, env: &Env) {
+        let sc_addr = ScAddress::try_from(self.address.clone()).unwrap();
+        if let (ScAddress::Account(account_id), Some(classic_account)) =
+            (sc_addr, &self.classic_account)
+        {
+            let (master, signers) = classic_account.signers.split_first().unwrap();
+            let signers: RustVec<_> = signers
+                .iter()
+                .map(|signer| (signer.key.verifying_key().to_bytes(), signer.weight))
+                .collect();
+            create_classic_account(
+                env,
+                &account_id,
+                master.weight,
+                classic_account.medium_threshold,
+                &signers,
+            );
         }
+    }
+}
 
-        signers
+/// Create the ledger entries of a classic account,
+/// with the public keys and weights of its signers besides the master key,
+/// and a trustline to the native token.
+pub(crate) fn create_classic_account(
+    env: &Env,
+    account_id: &AccountId,
+    master_weight: u8,
+    medium_threshold: u8,
+    signers: &[([u8; 32], u8)],
+) {
+    create_default_account(env, account_id, master_weight, medium_threshold, signers);
+    create_default_trustline(env, account_id);
+}
+
+fn account_signer(env: &Env, classic_account: ClassicAccount) -> TestSigner {
+    let signing_key = classic_account.signers[0].key.clone();
+    let verifying_key = signing_key.verifying_key().to_bytes();
+
+    let account_id = AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(verifying_key)));
+    let sc_address = ScAddress::Account(account_id);
+    let address = Address::try_from_val(env, &sc_address).unwrap();
+    TestSigner {
+        address,
+        key: Some(signing_key),
+        classic_account: Some(classic_account),
+        account_contract: None,
     }
+}
 
-    pub fn setup_account_storage(&self, env: &Env) {
-        let signers_n_bytes = self.generate_signers_with_bytes(&env);
-        signers_n_bytes.iter().for_each(|(signer, bytes)| {
-            let sc_addr = ScAddress::try_from(signer.address.clone()).unwrap();
-            match sc_addr {
-                ScAddress::Account(account_id) => {
-                    let signing_key = SigningKey::from_bytes(bytes);
-                    create_default_account(&env, &account_id, vec![(&signing_key, 100)]);
-                    create_default_trustline(&env, &account_id);
-                }
-                ScAddress::Contract(_) => {}
-            }
-        });
+fn contract_signer(
+    env: &Env,
+    signer_bytes: [u8; 32],
+    account_contract: AccountContract,
+) -> TestSigner {
+    let address = Address::try_from_val(env, &ScAddress::Contract(Hash(signer_bytes))).unwrap();
+    TestSigner {
+        address,
+        key: None,
+        classic_account: None,
+        account_contract: Some(account_contract),
     }
 }
 
-fn create_default_account(env: &Env, account_id: &AccountId, signers: Vec<(&SigningKey, u32)>) {
+fn create_default_account(
+    env: &Env,
+    account_id: &AccountId,
+    master_weight: u8,
+    medium_threshold: u8,
+    signers: &[([u8; 32], u8)],
+) {
     let key = LedgerKey::Account(LedgerKeyAccount {
         account_id: account_id.clone(),
     });
+    // Zero-weight signers aren't stored, as in the real ledger.
     let mut acc_signers = vec![];
-    for (signer, weight) in signers {
+    for (public_key, weight) in signers.iter().filter(|(_, weight)| *weight > 0) {
         acc_signers.push(Signer {
-            key: SignerKey::Ed25519(Uint256(signer.verifying_key().to_bytes())),
-            weight,
+            key: SignerKey::Ed25519(Uint256(*public_key)),
+            weight: *weight as u32,
         });
     }
 
@@ -118,11 +311,11 @@ fn create_default_account(env: &Env, account_id: &AccountId, signers: Vec<(&Sign
         account_id: account_id.clone(),
         balance: 10_000_000,
         seq_num: SequenceNumber(0),
-        num_sub_entries: 0,
+        num_sub_entries: acc_signers.len() as u32,
         inflation_dest: None,
         flags: 0,
         home_domain: Default::default(),
-        thresholds: Thresholds([1, 0, 0, 0]),
+        thresholds: Thresholds([master_weight, 0, medium_threshold, 0]),
         signers: acc_signers.try_into().unwrap(),
         ext,
     };
@@ -154,7 +347,7 @@ fn create_default_trustline(env: &Env, account_id: &AccountId) {
     let issuer = AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(issuer_bytes)));
     let asset = TrustLineAsset::CreditAlphanum4(AlphaNum4 {
         asset_code: AssetCode4([b'a', b'a', b'a', 0]),
-        issuer: issuer,
+        issuer,
     });
 
     let key = LedgerKey::Trustline(LedgerKeyTrustLine {
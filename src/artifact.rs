@@ -0,0 +1,806 @@
+//! Converting inputs to and from libFuzzer artifacts and text.
+//!
+//! An artifact is the raw bytes that `Input` is generated from
+//! with `arbitrary`. The text format is JSON,
+//! which can be edited by hand and converted back to an artifact
+//! that generates exactly the same `Input`.
+//!
+//! Converting back to bytes runs `arbitrary` in reverse:
+//! every `Encode` impl here writes the bytes that the type's
+//! `Arbitrary` impl reads, so the two must be kept in sync.
+
+use crate::accounts::ReentrantCall;
+use crate::addrgen::{AccountSignerType, AddressGenerator, AddressType};
+use crate::input::*;
+use crate::DAY_IN_LEDGERS;
+use soroban_sdk::testutils::arbitrary::arbitrary::{self, Arbitrary, Unstructured};
+use std::string::String as RustString;
+use std::vec::Vec as RustVec;
+
+impl Input {
+    /// Generate the input from a libFuzzer artifact,
+    /// as `fuzz_target!` does.
+    pub fn from_artifact(bytes: &[u8]) -> arbitrary::Result<Input> {
+        Input::arbitrary_take_rest(Unstructured::new(bytes))
+    }
+
+    /// The bytes of an artifact that generates this input.
+    ///
+    /// Fails if a value is outside the range
+    /// the fuzzer would generate it in.
+    pub fn to_artifact(&self) -> Result<RustVec<u8>, RustString> {
+        let mut encoder = Encoder::default();
+        self.encode(&mut encoder);
+        let mut bytes = encoder.finish()?;
+
+        // libFuzzer targets skip inputs shorter than this,
+        // and the bytes after the last transaction are ignored.
+        let min_len = Input::size_hint(0).0;
+        if bytes.len() < min_len {
+            bytes.resize(min_len, 0);
+        }
+
+        Ok(bytes)
+    }
+
+    pub fn from_text(text: &str) -> Result<Input, serde_json::Error> {
+        serde_json::from_str(text)
+    }
+
+    pub fn to_text(&self) -> RustString {
+        serde_json::to_string_pretty(self).expect("input serializes")
+    }
+}
+
+/// Writes the bytes that `Arbitrary` impls read.
+#[derive(Default)]
+pub(crate) struct Encoder {
+    bytes: RustVec<u8>,
+    error: Option<RustString>,
+}
+
+pub(crate) trait Encode {
+    fn encode(&self, e: &mut Encoder);
+}
+
+impl Encoder {
+    pub(crate) fn finish(self) -> Result<RustVec<u8>, RustString> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.bytes),
+        }
+    }
+
+    /// The inverse of `Arbitrary` for integers,
+    /// which read `size` bytes, little-endian.
+    fn int(&mut self, value: u128, size: usize) {
+        self.bytes.extend_from_slice(&value.to_le_bytes()[..size]);
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.int(value.into(), 1);
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(value.into());
+    }
+
+    /// The inverse of `Unstructured::int_in_range` for an integer
+    /// of `size` bytes, with the values as their two's complement bits.
+    ///
+    /// It reads big-endian just enough bytes to cover the range,
+    /// and takes the offset from the start modulo the range.
+    fn int_in_range(&mut self, name: &str, value: i128, start: i128, end: i128, size: usize) {
+        if !(start..=end).contains(&value) {
+            self.error
+                .get_or_insert(format!("{name} {value} is not in {start}..={end}"));
+            return;
+        }
+
+        let mask = u128::MAX >> (128 - size * 8);
+        let delta = (end as u128).wrapping_sub(start as u128) & mask;
+        let offset = (value as u128).wrapping_sub(start as u128) & mask;
+
+        let mut len = 0;
+        while len < size && delta >> (len * 8) > 0 {
+            len += 1;
+        }
+        for i in (0..len).rev() {
+            self.bytes.push((offset >> (i * 8)) as u8);
+        }
+    }
+
+    /// The inverse of `Unstructured::ratio`,
+    /// which is true when it reads 1 out of `1..=denominator`.
+    fn ratio(&mut self, value: bool, denominator: u8) {
+        self.bytes.push(if value { 0 } else { denominator - 1 });
+    }
+
+    /// The inverse of the derived `Arbitrary` for enums,
+    /// which scale a `u32` to the number of variants.
+    fn variant(&mut self, index: u64, count: u64) {
+        assert!(index < count, "variant {index} of {count}");
+        let x = (index << 32).div_ceil(count);
+        self.int(x.into(), 4);
+    }
+
+    /// The inverse of `Arbitrary` for `Vec`,
+    /// and of `arbitrary_take_rest` for `Vec`,
+    /// which read a flag before each element.
+    fn vec<T: Encode>(&mut self, values: &[T]) {
+        for value in values {
+            self.bool(true);
+            value.encode(self);
+        }
+        self.bool(false);
+    }
+
+    fn account_index(&mut self, index: usize) {
+        self.int_in_range(
+            "account index",
+            index as i128,
+            0,
+            ACCOUNT_INDEX_RANGE as i128 - 1,
+            8,
+        );
+    }
+
+    fn expiration_ledger(&mut self, ledger: u32) {
+        self.int_in_range(
+            "expiration ledger",
+            ledger.into(),
+            0,
+            (DAY_IN_LEDGERS * 30).into(),
+            4,
+        );
+    }
+
+    /// The inverse of `arbitrary_declined`,
+    /// which reads a flag before each index
+    /// unless there are already `MAX_NUMBER_OF_ADDRESSES`.
+    fn declined(&mut self, declined: &[usize]) {
+        if declined.len() > MAX_NUMBER_OF_ADDRESSES {
+            self.error.get_or_insert(format!(
+                "{} declined accounts is more than {MAX_NUMBER_OF_ADDRESSES}",
+                declined.len()
+            ));
+            return;
+        }
+        for index in declined {
+            self.ratio(false, 4);
+            self.account_index(*index);
+        }
+        if declined.len() < MAX_NUMBER_OF_ADDRESSES {
+            self.ratio(true, 4);
+        }
+    }
+
+    /// The inverse of `arbitrary_auth_mutation`
+    /// and `arbitrary_signature_expiration`.
+    fn rare_option<T: Encode>(&mut self, value: &Option<T>) {
+        self.ratio(value.is_some(), 10);
+        if let Some(value) = value {
+            value.encode(self);
+        }
+    }
+}
+
+impl Encode for u8 {
+    fn encode(&self, e: &mut Encoder) {
+        e.u8(*self);
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode(&self, e: &mut Encoder) {
+        for value in self {
+            value.encode(e);
+        }
+    }
+}
+
+impl Encode for Input {
+    fn encode(&self, e: &mut Encoder) {
+        self.address_generator.encode(e);
+        e.vec(&self.transactions);
+    }
+}
+
+impl Encode for AddressGenerator {
+    fn encode(&self, e: &mut Encoder) {
+        e.int_in_range(
+            "address seed",
+            self.address_seed.into(),
+            0,
+            (u64::MAX - MAX_NUMBER_OF_ADDRESSES as u64).into(),
+            8,
+        );
+        e.vec(&self.address_types);
+    }
+}
+
+impl Encode for AddressType {
+    fn encode(&self, e: &mut Encoder) {
+        let count = Self::VARIANTS;
+        match self {
+            AddressType::Account => e.variant(0, count),
+            AddressType::MultisigAccount {
+                master,
+                signers,
+                medium_threshold,
+            } => {
+                e.variant(1, count);
+                master.encode(e);
+                signers.encode(e);
+                e.u8(*medium_threshold);
+            }
+            AddressType::Contract => e.variant(2, count),
+            AddressType::Ed25519Contract => e.variant(3, count),
+            AddressType::MultisigContract { signing } => {
+                e.variant(4, count);
+                signing.iter().for_each(|signing| e.bool(*signing));
+            }
+            AddressType::PolicyContract {
+                denied_fn,
+                amount_limit,
+            } => {
+                e.variant(5, count);
+                e.u8(*denied_fn);
+                e.int(*amount_limit as u128, 16);
+            }
+            AddressType::ReentrantContract {
+                call,
+                propagate_error,
+            } => {
+                e.variant(6, count);
+                call.encode(e);
+                e.bool(*propagate_error);
+            }
+        }
+    }
+}
+
+impl Encode for AccountSignerType {
+    fn encode(&self, e: &mut Encoder) {
+        e.u8(self.weight);
+        e.bool(self.signing);
+    }
+}
+
+impl Encode for ReentrantCall {
+    fn encode(&self, e: &mut Encoder) {
+        let count = Self::VARIANTS;
+        match self {
+            ReentrantCall::Transfer(amount) => {
+                e.variant(0, count);
+                e.int(*amount as u128, 16);
+            }
+            ReentrantCall::Approve(amount) => {
+                e.variant(1, count);
+                e.int(*amount as u128, 16);
+            }
+            ReentrantCall::Balance => e.variant(2, count),
+        }
+    }
+}
+
+impl Encode for Transaction {
+    fn encode(&self, e: &mut Encoder) {
+        e.vec(&self.commands);
+        e.int_in_range(
+            "advance ledgers",
+            self.advance_ledgers.into(),
+            1,
+            DAY_IN_LEDGERS.into(),
+            4,
+        );
+        e.ratio(self.switch_network, 20);
+    }
+}
+
+impl Encode for Command {
+    fn encode(&self, e: &mut Encoder) {
+        let count = Self::VARIANTS;
+        match self {
+            Command::Mint(input) => {
+                e.variant(0, count);
+                input.encode(e);
+            }
+            Command::Approve(input) => {
+                e.variant(1, count);
+                input.encode(e);
+            }
+            Command::TransferFrom(input) => {
+                e.variant(2, count);
+                input.encode(e);
+            }
+            Command::Transfer(input) => {
+                e.variant(3, count);
+                input.encode(e);
+            }
+            Command::BurnFrom(input) => {
+                e.variant(4, count);
+                input.encode(e);
+            }
+            Command::Burn(input) => {
+                e.variant(5, count);
+                input.encode(e);
+            }
+            Command::ApproveAndTransferFrom(input) => {
+                e.variant(6, count);
+                input.encode(e);
+            }
+            Command::ApproveAndBurnFrom(input) => {
+                e.variant(7, count);
+                input.encode(e);
+            }
+            Command::Extension(input) => {
+                e.variant(8, count);
+                e.u8(input.command_index);
+                e.vec(&input.payload);
+            }
+            Command::ReplayAuth(input) => {
+                e.variant(9, count);
+                e.int(input.accepted_index.into(), 2);
+            }
+            Command::FreshAddress(input) => {
+                e.variant(10, count);
+                input.address_type.encode(e);
+            }
+        }
+    }
+}
+
+impl Encode for MintInput {
+    fn encode(&self, e: &mut Encoder) {
+        self.amount.encode(e);
+        e.account_index(self.to_account_index);
+        self.auth.encode(e);
+    }
+}
+
+impl Encode for ApproveInput {
+    fn encode(&self, e: &mut Encoder) {
+        self.amount.encode(e);
+        e.expiration_ledger(self.expiration_ledger);
+        e.account_index(self.from_account_index);
+        e.account_index(self.spender_account_index);
+        self.auth.encode(e);
+    }
+}
+
+impl Encode for TransferFromInput {
+    fn encode(&self, e: &mut Encoder) {
+        self.amount.encode(e);
+        e.account_index(self.spender_account_index);
+        e.account_index(self.from_account_index);
+        e.account_index(self.to_account_index);
+        self.auth.encode(e);
+    }
+}
+
+impl Encode for TransferInput {
+    fn encode(&self, e: &mut Encoder) {
+        self.amount.encode(e);
+        e.account_index(self.from_account_index);
+        e.account_index(self.to_account_index);
+        self.auth.encode(e);
+    }
+}
+
+impl Encode for BurnFromInput {
+    fn encode(&self, e: &mut Encoder) {
+        self.amount.encode(e);
+        e.account_index(self.spender_account_index);
+        e.account_index(self.from_account_index);
+        self.auth.encode(e);
+    }
+}
+
+impl Encode for BurnInput {
+    fn encode(&self, e: &mut Encoder) {
+        self.amount.encode(e);
+        e.account_index(self.from_account_index);
+        self.auth.encode(e);
+    }
+}
+
+impl Encode for ApproveAndTransferFromInput {
+    fn encode(&self, e: &mut Encoder) {
+        self.amount.encode(e);
+        e.expiration_ledger(self.expiration_ledger);
+        e.account_index(self.from_account_index);
+        e.account_index(self.spender_account_index);
+        e.account_index(self.to_account_index);
+        self.auth.encode(e);
+    }
+}
+
+impl Encode for ApproveAndBurnFromInput {
+    fn encode(&self, e: &mut Encoder) {
+        self.amount.encode(e);
+        e.expiration_ledger(self.expiration_ledger);
+        e.account_index(self.from_account_index);
+        e.account_index(self.spender_account_index);
+        e.account_index(self.to_account_index);
+        self.auth.encode(e);
+    }
+}
+
+impl Encode for CallAuth {
+    fn encode(&self, e: &mut Encoder) {
+        e.declined(&self.declined);
+        e.rare_option(&self.auth_mutation);
+        e.rare_option(&self.signature_expiration);
+        e.bool(self.via_proxy);
+    }
+}
+
+impl Encode for Amount {
+    fn encode(&self, e: &mut Encoder) {
+        let count = Self::VARIANTS;
+        match self {
+            Amount::Literal(amount) => {
+                e.variant(0, count);
+                e.int_in_range("amount", *amount, i128::MIN, i128::MAX, 16);
+            }
+            Amount::Balance => e.variant(1, count),
+            Amount::BalancePlusOne => e.variant(2, count),
+            Amount::Allowance => e.variant(3, count),
+            Amount::AllowanceMinusOne => e.variant(4, count),
+            Amount::Zero => e.variant(5, count),
+            Amount::One => e.variant(6, count),
+            Amount::Max => e.variant(7, count),
+        }
+    }
+}
+
+impl Encode for AuthMutation {
+    fn encode(&self, e: &mut Encoder) {
+        let count = Self::VARIANTS;
+        match self {
+            AuthMutation::FunctionName(i) => {
+                e.variant(0, count);
+                e.u8(*i);
+            }
+            AuthMutation::SwapArgs(i, j) => {
+                e.variant(1, count);
+                e.u8(*i);
+                e.u8(*j);
+            }
+            AuthMutation::AlterAmount(amount) => {
+                e.variant(2, count);
+                e.int(*amount as u128, 16);
+            }
+            AuthMutation::TruncateArgs => e.variant(3, count),
+            AuthMutation::ContractAddress => e.variant(4, count),
+            AuthMutation::ExpiredSignature => e.variant(5, count),
+            AuthMutation::DuplicateEntry => e.variant(6, count),
+            AuthMutation::ExtraEntry => e.variant(7, count),
+            AuthMutation::ReuseNonce(i) => {
+                e.variant(8, count);
+                e.int((*i).into(), 2);
+            }
+            AuthMutation::WrongKey(i) => {
+                e.variant(9, count);
+                e.u8(*i);
+            }
+            AuthMutation::FlipSignatureBit(i) => {
+                e.variant(10, count);
+                e.int((*i).into(), 2);
+            }
+            AuthMutation::TruncateSignatures => e.variant(11, count),
+            AuthMutation::WrongPublicKey(i, j) => {
+                e.variant(12, count);
+                e.u8(*i);
+                e.u8(*j);
+            }
+            AuthMutation::DuplicateSignature(i) => {
+                e.variant(13, count);
+                e.u8(*i);
+            }
+            AuthMutation::OtherNetwork(i) => {
+                e.variant(14, count);
+                e.u8(*i);
+            }
+        }
+    }
+}
+
+impl Encode for SignatureExpiration {
+    fn encode(&self, e: &mut Encoder) {
+        let count = Self::VARIANTS;
+        match self {
+            SignatureExpiration::Expired(n) => {
+                e.variant(0, count);
+                e.int((*n).into(), 2);
+            }
+            SignatureExpiration::CurrentLedger => e.variant(1, count),
+            SignatureExpiration::After(n) => {
+                e.variant(2, count);
+                e.int((*n).into(), 4);
+            }
+            SignatureExpiration::InsideMaxTtl(n) => {
+                e.variant(3, count);
+                e.int((*n).into(), 2);
+            }
+            SignatureExpiration::BeyondMaxTtl(n) => {
+                e.variant(4, count);
+                e.int((*n).into(), 2);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// Inputs generated from random bytes of various lengths.
+    fn arbitrary_inputs() -> RustVec<Input> {
+        let mut rng = StdRng::seed_from_u64(0);
+        let inputs: RustVec<Input> = (0..500)
+            .filter_map(|_| {
+                let mut bytes = vec![0; rng.gen_range(0..2048)];
+                rng.fill(&mut bytes[..]);
+                Input::from_artifact(&bytes).ok()
+            })
+            .collect();
+        assert!(inputs.len() > 400, "too few inputs decoded");
+        inputs
+    }
+
+    /// The input in `testdata/transfer.artifact` and `testdata/transfer.json`.
+    fn transfer_input() -> Input {
+        Input {
+            address_generator: AddressGenerator {
+                address_seed: 1,
+                address_types: vec![AddressType::Account, AddressType::Contract],
+            },
+            transactions: vec![Transaction {
+                commands: vec![
+                    Command::Mint(MintInput {
+                        amount: Amount::Literal(100),
+                        to_account_index: 1,
+                        auth: CallAuth {
+                            declined: vec![],
+                            auth_mutation: None,
+                            signature_expiration: None,
+                            via_proxy: false,
+                        },
+                    }),
+                    Command::Transfer(TransferInput {
+                        amount: Amount::Balance,
+                        from_account_index: 1,
+                        to_account_index: 2,
+                        auth: CallAuth {
+                            declined: vec![1],
+                            auth_mutation: Some(AuthMutation::AlterAmount(-1)),
+                            signature_expiration: Some(SignatureExpiration::After(5)),
+                            via_proxy: true,
+                        },
+                    }),
+                ],
+                advance_ledgers: DAY_IN_LEDGERS,
+                switch_network: false,
+            }],
+        }
+    }
+
+    #[test]
+    fn artifact_round_trip() {
+        for input in arbitrary_inputs() {
+            let artifact = input.to_artifact().unwrap();
+            assert_eq!(Input::from_artifact(&artifact).unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn text_round_trip() {
+        for input in arbitrary_inputs() {
+            assert_eq!(Input::from_text(&input.to_text()).unwrap(), input);
+        }
+    }
+
+    /// Assert that `values` has one of each of `variants` variants.
+    fn assert_every_variant<T>(values: &[T], variants: u64) {
+        let distinct: std::collections::HashSet<_> =
+            values.iter().map(std::mem::discriminant).collect();
+        assert_eq!(distinct.len() as u64, variants);
+    }
+
+    #[test]
+    fn every_variant_round_trips() {
+        let reentrant_calls = [
+            ReentrantCall::Transfer(-1),
+            ReentrantCall::Approve(i128::MAX),
+            ReentrantCall::Balance,
+        ];
+        let signer = AccountSignerType {
+            weight: 1,
+            signing: true,
+        };
+        let mut address_types = vec![
+            AddressType::Account,
+            AddressType::MultisigAccount {
+                master: signer.clone(),
+                signers: [signer.clone(), signer],
+                medium_threshold: 2,
+            },
+            AddressType::Contract,
+            AddressType::Ed25519Contract,
+            AddressType::MultisigContract {
+                signing: [true, false, true],
+            },
+            AddressType::PolicyContract {
+                denied_fn: 3,
+                amount_limit: i128::MIN,
+            },
+        ];
+        address_types.extend(
+            reentrant_calls
+                .iter()
+                .map(|call| AddressType::ReentrantContract {
+                    call: call.clone(),
+                    propagate_error: true,
+                }),
+        );
+
+        let amounts = [
+            Amount::Literal(-100),
+            Amount::Balance,
+            Amount::BalancePlusOne,
+            Amount::Allowance,
+            Amount::AllowanceMinusOne,
+            Amount::Zero,
+            Amount::One,
+            Amount::Max,
+        ];
+        let auth_mutations = [
+            AuthMutation::FunctionName(1),
+            AuthMutation::SwapArgs(0, 2),
+            AuthMutation::AlterAmount(i128::MIN),
+            AuthMutation::TruncateArgs,
+            AuthMutation::ContractAddress,
+            AuthMutation::ExpiredSignature,
+            AuthMutation::DuplicateEntry,
+            AuthMutation::ExtraEntry,
+            AuthMutation::ReuseNonce(300),
+            AuthMutation::WrongKey(4),
+            AuthMutation::FlipSignatureBit(500),
+            AuthMutation::TruncateSignatures,
+            AuthMutation::WrongPublicKey(5, 6),
+            AuthMutation::DuplicateSignature(7),
+            AuthMutation::OtherNetwork(8),
+        ];
+        let signature_expirations = [
+            SignatureExpiration::Expired(1),
+            SignatureExpiration::CurrentLedger,
+            SignatureExpiration::After(u32::MAX),
+            SignatureExpiration::InsideMaxTtl(2),
+            SignatureExpiration::BeyondMaxTtl(3),
+        ];
+
+        let auth = CallAuth {
+            declined: vec![0, ACCOUNT_INDEX_RANGE - 1],
+            auth_mutation: None,
+            signature_expiration: None,
+            via_proxy: true,
+        };
+        let mut commands = vec![
+            Command::Mint(MintInput {
+                amount: Amount::One,
+                to_account_index: 1,
+                auth: auth.clone(),
+            }),
+            Command::Approve(ApproveInput {
+                amount: Amount::One,
+                expiration_ledger: 10,
+                from_account_index: 1,
+                spender_account_index: 2,
+                auth: auth.clone(),
+            }),
+            Command::TransferFrom(TransferFromInput {
+                amount: Amount::One,
+                spender_account_index: 2,
+                from_account_index: 1,
+                to_account_index: 0,
+                auth: auth.clone(),
+            }),
+            Command::BurnFrom(BurnFromInput {
+                amount: Amount::One,
+                spender_account_index: 2,
+                from_account_index: 1,
+                auth: auth.clone(),
+            }),
+            Command::Burn(BurnInput {
+                amount: Amount::One,
+                from_account_index: 1,
+                auth: auth.clone(),
+            }),
+            Command::ApproveAndTransferFrom(ApproveAndTransferFromInput {
+                amount: Amount::One,
+                expiration_ledger: 10,
+                from_account_index: 1,
+                spender_account_index: 2,
+                to_account_index: 0,
+                auth: auth.clone(),
+            }),
+            Command::ApproveAndBurnFrom(ApproveAndBurnFromInput {
+                amount: Amount::One,
+                expiration_ledger: 10,
+                from_account_index: 1,
+                spender_account_index: 2,
+                to_account_index: 0,
+                auth: auth.clone(),
+            }),
+            Command::Extension(ExtensionInput {
+                command_index: 1,
+                payload: vec![1, 2, 3],
+            }),
+            Command::ReplayAuth(ReplayAuthInput {
+                accepted_index: 400,
+            }),
+            Command::FreshAddress(FreshAddressInput {
+                address_type: AddressType::Contract,
+            }),
+        ];
+        // One transfer for each amount, auth mutation and expiration.
+        let transfer = |amount: &Amount, auth: CallAuth| {
+            Command::Transfer(TransferInput {
+                amount: amount.clone(),
+                from_account_index: 0,
+                to_account_index: 1,
+                auth,
+            })
+        };
+        commands.extend(amounts.iter().map(|amount| transfer(amount, auth.clone())));
+        commands.extend(auth_mutations.iter().map(|mutation| {
+            let auth = CallAuth {
+                auth_mutation: Some(mutation.clone()),
+                ..auth.clone()
+            };
+            transfer(&Amount::One, auth)
+        }));
+        commands.extend(signature_expirations.iter().map(|expiration| {
+            let auth = CallAuth {
+                signature_expiration: Some(expiration.clone()),
+                ..auth.clone()
+            };
+            transfer(&Amount::One, auth)
+        }));
+
+        assert_every_variant(&reentrant_calls, ReentrantCall::VARIANTS);
+        assert_every_variant(&address_types, AddressType::VARIANTS);
+        assert_every_variant(&amounts, Amount::VARIANTS);
+        assert_every_variant(&auth_mutations, AuthMutation::VARIANTS);
+        assert_every_variant(&signature_expirations, SignatureExpiration::VARIANTS);
+        assert_every_variant(&commands, Command::VARIANTS);
+
+        let input = Input {
+            address_generator: AddressGenerator {
+                address_seed: 2,
+                address_types,
+            },
+            transactions: vec![Transaction {
+                commands,
+                advance_ledgers: 1,
+                switch_network: true,
+            }],
+        };
+        let artifact = input.to_artifact().unwrap();
+        assert_eq!(Input::from_artifact(&artifact).unwrap(), input);
+    }
+
+    #[test]
+    fn known_artifact() {
+        let artifact = include_bytes!("../testdata/transfer.artifact");
+        assert_eq!(Input::from_artifact(artifact).unwrap(), transfer_input());
+        assert_eq!(transfer_input().to_artifact().unwrap(), artifact);
+
+        let text = include_str!("../testdata/transfer.json");
+        assert_eq!(Input::from_text(text).unwrap(), transfer_input());
+    }
+}
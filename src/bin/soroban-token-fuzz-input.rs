@@ -0,0 +1,51 @@
+//! Convert fuzzer inputs between libFuzzer artifacts and text.
+//!
+//! ```text
+//! soroban-token-fuzz-input to-text <artifact>
+//! soroban-token-fuzz-input to-artifact <text file> <artifact>
+//! ```
+//!
+//! `to-text` prints the input an artifact generates as JSON,
+//! which can be edited and converted back to an artifact
+//! with `to-artifact`, or loaded with `Input::from_text`.
+
+use soroban_token_fuzzer::Input;
+use std::process::exit;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        ["to-text", artifact_path] => {
+            let bytes = read(artifact_path);
+            let input = Input::from_artifact(&bytes)
+                .unwrap_or_else(|e| fail(&format!("generating input from `{artifact_path}`: {e}")));
+            println!("{}", input.to_text());
+        }
+        ["to-artifact", text_path, artifact_path] => {
+            let text = String::from_utf8(read(text_path))
+                .unwrap_or_else(|e| fail(&format!("reading `{text_path}`: {e}")));
+            let input = Input::from_text(&text)
+                .unwrap_or_else(|e| fail(&format!("parsing `{text_path}`: {e}")));
+            let bytes = input
+                .to_artifact()
+                .unwrap_or_else(|e| fail(&format!("encoding `{text_path}`: {e}")));
+            std::fs::write(artifact_path, bytes)
+                .unwrap_or_else(|e| fail(&format!("writing `{artifact_path}`: {e}")));
+        }
+        _ => fail(
+            "usage: soroban-token-fuzz-input to-text <artifact>\n       \
+             soroban-token-fuzz-input to-artifact <text file> <artifact>",
+        ),
+    }
+}
+
+fn read(path: &str) -> Vec<u8> {
+    std::fs::read(path).unwrap_or_else(|e| fail(&format!("reading `{path}`: {e}")))
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{message}");
+    exit(1)
+}
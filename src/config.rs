@@ -1,7 +1,25 @@
+use crate::addrgen::TestSigner;
+use crate::input::{DEFAULT_NUMBER_OF_ADDRESSES, MAX_NUMBER_OF_ADDRESSES};
+use crate::model::{ContractState, TokenModel};
+use serde::Deserialize;
+use soroban_sdk::testutils::arbitrary::arbitrary::{Arbitrary, Unstructured};
 use soroban_sdk::token::StellarAssetClient;
-use soroban_sdk::xdr::SorobanAuthorizationEntry;
-use soroban_sdk::{Address, Env};
+use soroban_sdk::xdr::{
+    ContractDataDurability, ContractDataEntry, ContractExecutable, Hash, LedgerEntryData,
+    LedgerKey, LedgerKeyContractData, ScAddress, ScErrorCode, ScErrorType, ScVal,
+    SorobanAuthorizationEntry,
+};
+use soroban_sdk::{Address, Bytes, Env, IntoVal, String, Symbol};
 use soroban_sdk::{Error, InvokeError, TryFromVal, Val};
+use std::rc::Rc;
+use std::string::String as RustString;
+use std::sync::Arc;
+use std::vec::Vec as RustVec;
+
+/// The result of calling a token method that returns nothing
+/// through a generated client's `try_` method.
+pub type TokenContractResult =
+    Result<Result<(), <() as TryFromVal<Env, Val>>::Error>, Result<Error, InvokeError>>;
 
 /// Token-specific configuration and customization.
 ///
@@ -9,19 +27,149 @@ use soroban_sdk::{Error, InvokeError, TryFromVal, Val};
 /// [`Config::contract`] constructor, providing
 /// an implementation of [`ContractTokenOps`]
 /// customized to their token.
+///
+/// Tokens that are only available as compiled WASM
+/// can instead use [`Config::wasm`].
 pub struct Config {
     kind: TokenKind,
+    mint: Option<FnSpec>,
+    model: Option<Box<dyn Fn() -> Box<dyn TokenModel>>>,
+    invariants: RustVec<Invariant>,
+    error_codes: ErrorCodes,
+    number_of_addresses: usize,
+    extension_commands: RustVec<ExtensionCommand>,
+}
+
+/// The errors a token returns for the failures the model can predict.
+///
+/// Codes from a `contracterror` enum are written as
+/// `Error::from_contract_error(code)`.
+/// A failure can have several errors, any of which the token may return.
+/// Failures with no errors are only expected to fail,
+/// with any error.
+///
+/// When a call would fail for several reasons,
+/// the fuzzer accepts an error for any of them,
+/// since tokens check for them in different orders.
+#[derive(Clone, Debug, Default)]
+pub struct ErrorCodes {
+    pub negative_amount: RustVec<Error>,
+    /// Usually `Error(Context, InvalidAction)`,
+    /// which is how the host reports a failed `require_auth`
+    /// to the caller, as with any error that isn't a contract error.
+    pub unauthorized: RustVec<Error>,
+    pub insufficient_allowance: RustVec<Error>,
+    pub insufficient_balance: RustVec<Error>,
+}
+
+impl ErrorCodes {
+    /// The errors returned by the Stellar Asset Contract.
+    pub fn stellar_asset() -> ErrorCodes {
+        ErrorCodes {
+            negative_amount: vec![Error::from_contract_error(8)],
+            unauthorized: vec![Error::from_type_and_code(
+                ScErrorType::Context,
+                ScErrorCode::InvalidAction,
+            )],
+            insufficient_allowance: vec![Error::from_contract_error(9)],
+            // BalanceError, or OverflowError when a classic account,
+            // whose balance is an i64, spends more than `i64::MAX`.
+            insufficient_balance: vec![
+                Error::from_contract_error(10),
+                Error::from_contract_error(12),
+            ],
+        }
+    }
+}
+
+/// A token-specific invariant, added with [`Config::with_invariant`].
+pub struct Invariant {
+    name: RustString,
+    check: Box<InvariantFn>,
+}
+
+type InvariantFn = dyn Fn(&Env, &Address, &[TestSigner], &dyn TokenModel) -> Result<(), RustString>;
+
+impl Invariant {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn check(
+        &self,
+        env: &Env,
+        token_contract_id: &Address,
+        accounts: &[TestSigner],
+        model: &dyn TokenModel,
+    ) -> Result<(), RustString> {
+        (self.check)(env, token_contract_id, accounts, model)
+    }
 }
 
 pub enum TokenKind {
     Native,
     Contract(ContractTokenConfig),
+    Wasm(WasmTokenConfig),
 }
 
 pub struct ContractTokenConfig {
     ops: Box<dyn ContractTokenOps>,
 }
 
+pub struct WasmTokenConfig {
+    wasm: Arc<[u8]>,
+    init: FnSpec,
+}
+
+/// A call to a contract function, described by name and arguments.
+///
+/// This is used to call into tokens for which we
+/// don't have a generated client, like tokens registered
+/// from WASM, and to bind the admin's mint method,
+/// whatever it is called.
+///
+/// In JSON this is written as e.g.
+/// `{ "fn_name": "mint", "args": ["to", "amount"] }`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct FnSpec {
+    pub fn_name: RustString,
+    pub args: RustVec<ArgSpec>,
+}
+
+/// An argument to a [`FnSpec`].
+///
+/// In JSON placeholders are written as strings, e.g. `"admin"`,
+/// and values as objects, e.g. `{ "u32": 7 }`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArgSpec {
+    /// The admin address chosen by the fuzzer.
+    Admin,
+    /// The recipient of a mint.
+    ///
+    /// Only valid in the mint binding.
+    To,
+    /// The amount of a mint.
+    ///
+    /// Only valid in the mint binding.
+    Amount,
+    Bool(bool),
+    U32(u32),
+    U64(u64),
+    I64(i64),
+    I128(i128),
+    String(RustString),
+    Symbol(RustString),
+    Bytes(RustVec<u8>),
+}
+
+/// The values substituted for placeholder [`ArgSpec`]s.
+pub struct ArgValues<'a> {
+    pub admin: &'a Address,
+    pub to: Option<&'a Address>,
+    pub amount: Option<i128>,
+}
+
 pub trait ContractTokenOps {
     /// Register the contract with the environment and perform
     /// contract-specific one-time initialization.
@@ -37,51 +185,285 @@ pub trait ContractTokenOps {
     fn reregister_contract(&self, env: &Env, token_contract_id: &Address);
 
     /// Create an admin client.
+    ///
+    /// This does not need to be implemented if the mint method
+    /// is bound with [`Config::with_mint`].
     fn new_admin_client<'a>(
+        &self,
+        _env: &Env,
+        _token_contract_id: &Address,
+    ) -> Box<dyn TokenAdminClient<'a> + 'a> {
+        panic!("no admin client - implement `new_admin_client` or call `Config::with_mint`")
+    }
+
+    /// Token-specific commands to fuzz alongside
+    /// the standard `TokenInterface` methods,
+    /// in addition to those added with [`Config::with_extension_command`].
+    ///
+    /// This will be called once per fuzz run.
+    fn extension_commands(&self) -> RustVec<ExtensionCommand> {
+        RustVec::new()
+    }
+}
+
+/// A token-specific command, e.g. to freeze an account or set a fee.
+///
+/// The fuzzer generates a payload for the command
+/// and runs it between the standard commands.
+#[derive(Clone)]
+pub struct ExtensionCommand {
+    name: RustString,
+    exec: Rc<ExtensionCommandFn>,
+}
+
+type ExtensionCommandFn = dyn Fn(&Env, &Address, &[TestSigner], &mut dyn TokenModel, &[u8]);
+
+impl ExtensionCommand {
+    /// Create a command that generates a `T` and passes it to `exec`,
+    /// along with the token address and the fuzzer's accounts.
+    ///
+    /// `exec` calls the token, asserts whatever it expects of the result,
+    /// and updates the model to match.
+    /// It is responsible for setting up authorization,
+    /// e.g. with `Env::mock_all_auths`.
+    ///
+    /// Payload bytes that `T` can't be generated from
+    /// are read as much as possible,
+    /// and failing that `T` is generated from no bytes at all.
+    /// `T` must be able to be generated from no bytes,
+    /// as derived `Arbitrary` implementations are.
+    pub fn new<T, F>(name: &str, exec: F) -> ExtensionCommand
+    where
+        T: for<'u> Arbitrary<'u>,
+        F: Fn(&Env, &Address, &[TestSigner], &mut dyn TokenModel, T) + 'static,
+    {
+        let command_name = name.to_string();
+        ExtensionCommand {
+            name: name.to_string(),
+            exec: Rc::new(move |env, token_contract_id, accounts, model, payload| {
+                let payload = T::arbitrary_take_rest(Unstructured::new(payload))
+                    .or_else(|_| T::arbitrary(&mut Unstructured::new(payload)))
+                    .or_else(|_| T::arbitrary(&mut Unstructured::new(&[])))
+                    .unwrap_or_else(|e| {
+                        panic!("the payload of `{command_name}` can't be generated: {e}")
+                    });
+                exec(env, token_contract_id, accounts, model, payload);
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn exec(
         &self,
         env: &Env,
         token_contract_id: &Address,
-    ) -> Box<dyn TokenAdminClient<'a> + 'a>;
+        accounts: &[TestSigner],
+        model: &mut dyn TokenModel,
+        payload: &[u8],
+    ) {
+        (self.exec)(env, token_contract_id, accounts, model, payload)
+    }
 }
 
 pub trait TokenAdminClient<'a> {
     /// Mint tokens.
-    fn try_mint(
-        &self,
-        to: &Address,
-        amount: &i128,
-    ) -> Result<Result<(), <() as TryFromVal<Env, Val>>::Error>, Result<Error, InvokeError>>;
+    fn try_mint(&self, to: &Address, amount: &i128) -> TokenContractResult;
 
-    /// Unused.
+    /// Create a client that sets `auths` in the environment
+    /// before every call, like the generated clients' `set_auths`.
     ///
-    /// This is just defined to make sure the lifetimes work;
-    /// we don't actually need to implement it yet.
-    fn set_auths(&self, _auths: &'a [SorobanAuthorizationEntry]) -> Box<dyn TokenAdminClient> {
-        todo!()
-    }
+    /// The fuzzer uses this to sign admin calls
+    /// the same way it signs calls by other users.
+    fn set_auths<'b>(
+        &self,
+        auths: &'b [SorobanAuthorizationEntry],
+    ) -> Box<dyn TokenAdminClient<'b> + 'b>;
 }
 
 struct NativeTokenAdminClient<'a> {
     admin_client: StellarAssetClient<'a>,
 }
 
+/// Calls the bound mint method with a dynamic contract invocation.
+#[derive(Clone)]
+struct BoundTokenAdminClient {
+    env: Env,
+    token_contract_id: Address,
+    admin: Address,
+    mint: FnSpec,
+    auths: Option<RustVec<SorobanAuthorizationEntry>>,
+}
+
 impl Config {
     pub fn native() -> Config {
         Config {
             kind: TokenKind::Native,
+            mint: None,
+            model: None,
+            invariants: RustVec::new(),
+            error_codes: ErrorCodes::stellar_asset(),
+            number_of_addresses: DEFAULT_NUMBER_OF_ADDRESSES,
+            extension_commands: RustVec::new(),
         }
     }
 
     pub fn contract(ops: impl ContractTokenOps + 'static) -> Config {
         Config {
             kind: TokenKind::Contract(ContractTokenConfig { ops: Box::new(ops) }),
+            mint: None,
+            model: None,
+            invariants: RustVec::new(),
+            error_codes: ErrorCodes::default(),
+            number_of_addresses: DEFAULT_NUMBER_OF_ADDRESSES,
+            extension_commands: RustVec::new(),
+        }
+    }
+
+    /// Fuzz a token from its compiled WASM.
+    ///
+    /// The contract is initialized by calling `init`.
+    /// Unless bound otherwise with [`Config::with_mint`],
+    /// it must have a `mint(to, amount)` method
+    /// that requires the admin's authorization.
+    ///
+    /// A config is made for every fuzz run, so to avoid copying
+    /// the WASM each time, pass it as an `Arc<[u8]>` and clone that.
+    pub fn wasm(wasm: impl Into<Arc<[u8]>>, init: FnSpec) -> Config {
+        Config {
+            kind: TokenKind::Wasm(WasmTokenConfig {
+                wasm: wasm.into(),
+                init,
+            }),
+            mint: None,
+            model: None,
+            invariants: RustVec::new(),
+            error_codes: ErrorCodes::default(),
+            number_of_addresses: DEFAULT_NUMBER_OF_ADDRESSES,
+            extension_commands: RustVec::new(),
         }
     }
 
+    /// Bind the token's mint method.
+    ///
+    /// The fuzzer calls this method dynamically instead of
+    /// through the admin client, with the arguments in `mint`,
+    /// and expects it to require the admin's authorization.
+    /// `mint` should contain [`ArgSpec::To`] and [`ArgSpec::Amount`].
+    pub fn with_mint(mut self, mint: FnSpec) -> Config {
+        self.mint = Some(mint);
+        self
+    }
+
+    /// Use a different model of the token's expected state.
+    ///
+    /// `new_model` is called at the start of every fuzz run.
+    /// By default the model is [`ContractState`].
+    pub fn with_model<M, F>(mut self, new_model: F) -> Config
+    where
+        M: TokenModel + 'static,
+        F: Fn() -> M + 'static,
+    {
+        self.model = Some(Box::new(move || Box::new(new_model())));
+        self
+    }
+
+    /// Check a token-specific invariant at the end of every transaction,
+    /// after the standard assertions.
+    ///
+    /// `check` returns an error describing the violation, if any,
+    /// and the fuzzer panics with the invariant's name and the error.
+    pub fn with_invariant<F>(mut self, name: &str, check: F) -> Config
+    where
+        F: Fn(&Env, &Address, &[TestSigner], &dyn TokenModel) -> Result<(), RustString> + 'static,
+    {
+        self.invariants.push(Invariant {
+            name: name.to_string(),
+            check: Box::new(check),
+        });
+        self
+    }
+
+    /// Fuzz a token-specific command alongside
+    /// the standard `TokenInterface` methods.
+    ///
+    /// This works for every kind of token,
+    /// including those from [`Config::wasm`],
+    /// which have no [`ContractTokenOps`] to return commands from.
+    pub fn with_extension_command(mut self, command: ExtensionCommand) -> Config {
+        self.extension_commands.push(command);
+        self
+    }
+
+    /// Declare the errors the token returns for predictable failures.
+    ///
+    /// By default no codes are checked, except for the native token,
+    /// which uses [`ErrorCodes::stellar_asset`].
+    pub fn with_error_codes(mut self, error_codes: ErrorCodes) -> Config {
+        self.error_codes = error_codes;
+        self
+    }
+
+    /// Start every run with this many addresses,
+    /// instead of [`DEFAULT_NUMBER_OF_ADDRESSES`].
+    ///
+    /// The first address is the admin.
+    /// More addresses can be introduced during a run,
+    /// up to [`MAX_NUMBER_OF_ADDRESSES`].
+    pub fn with_number_of_addresses(mut self, number_of_addresses: usize) -> Config {
+        assert!(
+            (1..=MAX_NUMBER_OF_ADDRESSES).contains(&number_of_addresses),
+            "number of addresses must be from 1 to {MAX_NUMBER_OF_ADDRESSES}"
+        );
+        self.number_of_addresses = number_of_addresses;
+        self
+    }
+
+    pub fn number_of_addresses(&self) -> usize {
+        self.number_of_addresses
+    }
+
+    pub fn error_codes(&self) -> &ErrorCodes {
+        &self.error_codes
+    }
+
+    pub fn invariants(&self) -> &[Invariant] {
+        &self.invariants
+    }
+
+    pub fn new_model(&self) -> Box<dyn TokenModel> {
+        match &self.model {
+            Some(new_model) => new_model(),
+            None => Box::new(ContractState::init()),
+        }
+    }
+
+    /// The commands from [`ContractTokenOps::extension_commands`],
+    /// followed by those added with [`Config::with_extension_command`].
+    pub fn extension_commands(&self) -> RustVec<ExtensionCommand> {
+        let mut commands = match &self.kind {
+            TokenKind::Contract(cfg) => cfg.ops.extension_commands(),
+            TokenKind::Native | TokenKind::Wasm(_) => RustVec::new(),
+        };
+        commands.extend(self.extension_commands.iter().cloned());
+        commands
+    }
+
+    /// The mint method, as bound by [`Config::with_mint`],
+    /// or `mint(to, amount)` by default.
+    pub fn mint_spec(&self) -> FnSpec {
+        self.mint
+            .clone()
+            .unwrap_or_else(|| FnSpec::new("mint", vec![ArgSpec::To, ArgSpec::Amount]))
+    }
+
     pub fn register_contract_init(&self, env: &Env, admin: &Address) -> Address {
         match &self.kind {
             TokenKind::Native => env.register_stellar_asset_contract(admin.clone()),
             TokenKind::Contract(cfg) => cfg.register_contract_init(env, admin),
+            TokenKind::Wasm(cfg) => cfg.register_contract_init(env, admin),
         }
     }
 
@@ -89,6 +471,7 @@ impl Config {
         match &self.kind {
             TokenKind::Native => { /* nop */ }
             TokenKind::Contract(cfg) => cfg.reregister_contract(env, token_contract_id),
+            TokenKind::Wasm(cfg) => cfg.reregister_contract(env, token_contract_id),
         }
     }
 
@@ -96,27 +479,74 @@ impl Config {
         &self,
         env: &Env,
         token_contract_id: &Address,
+        admin: &Address,
     ) -> Box<dyn TokenAdminClient<'a> + 'a> {
+        let bound_admin_client = || {
+            Box::new(BoundTokenAdminClient {
+                env: env.clone(),
+                token_contract_id: token_contract_id.clone(),
+                admin: admin.clone(),
+                mint: self.mint_spec(),
+                auths: None,
+            })
+        };
+
+        if self.mint.is_some() {
+            return bound_admin_client();
+        }
+
         match &self.kind {
             TokenKind::Native => Box::new(NativeTokenAdminClient {
-                admin_client: { StellarAssetClient::new(env, &token_contract_id) },
+                admin_client: { StellarAssetClient::new(env, token_contract_id) },
             }),
             TokenKind::Contract(cfg) => cfg.new_admin_client(env, token_contract_id),
+            TokenKind::Wasm(_) => bound_admin_client(),
         }
     }
 }
 
 impl<'a> TokenAdminClient<'a> for NativeTokenAdminClient<'a> {
-    fn try_mint(
-        &self,
-        to: &Address,
-        amount: &i128,
-    ) -> Result<Result<(), <() as TryFromVal<Env, Val>>::Error>, Result<Error, InvokeError>> {
+    fn try_mint(&self, to: &Address, amount: &i128) -> TokenContractResult {
         self.admin_client.try_mint(to, amount)
     }
 
-    fn set_auths(&self, _auths: &'a [SorobanAuthorizationEntry]) -> Box<dyn TokenAdminClient> {
-        todo!()
+    fn set_auths<'b>(
+        &self,
+        auths: &'b [SorobanAuthorizationEntry],
+    ) -> Box<dyn TokenAdminClient<'b> + 'b> {
+        let admin_client = &self.admin_client;
+        Box::new(NativeTokenAdminClient {
+            admin_client: StellarAssetClient::new(&admin_client.env, &admin_client.address)
+                .set_auths(auths),
+        })
+    }
+}
+
+impl<'a> TokenAdminClient<'a> for BoundTokenAdminClient {
+    fn try_mint(&self, to: &Address, amount: &i128) -> TokenContractResult {
+        let values = ArgValues {
+            admin: &self.admin,
+            to: Some(to),
+            amount: Some(*amount),
+        };
+        if let Some(auths) = &self.auths {
+            self.env.set_auths(auths);
+        }
+        self.env.try_invoke_contract::<(), Error>(
+            &self.token_contract_id,
+            &Symbol::new(&self.env, &self.mint.fn_name),
+            self.mint.args(&self.env, &values),
+        )
+    }
+
+    fn set_auths<'b>(
+        &self,
+        auths: &'b [SorobanAuthorizationEntry],
+    ) -> Box<dyn TokenAdminClient<'b> + 'b> {
+        Box::new(BoundTokenAdminClient {
+            auths: Some(auths.to_vec()),
+            ..self.clone()
+        })
     }
 }
 
@@ -137,3 +567,246 @@ impl ContractTokenConfig {
         self.ops.new_admin_client(env, token_contract_id)
     }
 }
+
+impl WasmTokenConfig {
+    pub fn register_contract_init(&self, env: &Env, admin: &Address) -> Address {
+        let token_contract_id = env.register_contract_wasm(None, &*self.wasm);
+
+        let values = ArgValues {
+            admin,
+            to: None,
+            amount: None,
+        };
+        let r = env.try_invoke_contract::<Val, Error>(
+            &token_contract_id,
+            &Symbol::new(env, &self.init.fn_name),
+            self.init.args(env, &values),
+        );
+        assert!(r.is_ok(), "calling `{}` failed: {r:?}", self.init.fn_name);
+
+        token_contract_id
+    }
+
+    /// Make sure the contract's code is installed in a new `Env`.
+    ///
+    /// [`ContractTokenOps::reregister_contract`] implementations
+    /// call `register_contract` because a native contract's
+    /// implementation lives in the `Env`, not in the ledger,
+    /// and is lost when `Env::from_snapshot` rebuilds it.
+    /// A WASM contract is entirely in the ledger snapshot:
+    /// its instance, which names the hash of its code,
+    /// and the code itself. So this only uploads the WASM
+    /// with `upload_contract_wasm`, in case the code expired
+    /// while advancing time. Registering it again with
+    /// `register_contract_wasm` would replace its instance storage.
+    ///
+    /// # Panics
+    ///
+    /// If the instance expired, or doesn't run the uploaded code,
+    /// since the token could then only run against stale code or none.
+    pub fn reregister_contract(&self, env: &Env, token_contract_id: &Address) {
+        let wasm_hash = env.deployer().upload_contract_wasm(&*self.wasm);
+        let expected = ContractExecutable::Wasm(Hash(wasm_hash.to_array()));
+        assert_eq!(
+            contract_executable(env, token_contract_id),
+            Some(expected),
+            "the token's instance doesn't run its WASM"
+        );
+    }
+}
+
+/// The code a contract's instance runs, if the instance is in the ledger.
+fn contract_executable(env: &Env, contract_id: &Address) -> Option<ContractExecutable> {
+    let key = Rc::new(LedgerKey::ContractData(LedgerKeyContractData {
+        contract: ScAddress::try_from(contract_id).expect("address"),
+        key: ScVal::LedgerKeyContractInstance,
+        durability: ContractDataDurability::Persistent,
+    }));
+    env.host()
+        .with_mut_storage(|storage| {
+            let budget = env.host().budget_cloned();
+            if !storage.has(&key, &budget)? {
+                return Ok(None);
+            }
+            let entry = storage.get(&key, &budget)?;
+            let LedgerEntryData::ContractData(ContractDataEntry {
+                val: ScVal::ContractInstance(instance),
+                ..
+            }) = &entry.data
+            else {
+                panic!("the contract instance key holds {:?}", entry.data);
+            };
+            Ok(Some(instance.executable.clone()))
+        })
+        .expect("contract instance")
+}
+
+impl FnSpec {
+    pub fn new(fn_name: &str, args: RustVec<ArgSpec>) -> FnSpec {
+        FnSpec {
+            fn_name: fn_name.to_string(),
+            args,
+        }
+    }
+
+    /// Build the argument vector for a call.
+    pub fn args(&self, env: &Env, values: &ArgValues) -> soroban_sdk::Vec<Val> {
+        let mut args = soroban_sdk::Vec::new(env);
+        for arg in &self.args {
+            args.push_back(arg.to_val(env, values));
+        }
+        args
+    }
+}
+
+impl ArgSpec {
+    fn to_val(&self, env: &Env, values: &ArgValues) -> Val {
+        match self {
+            ArgSpec::Admin => values.admin.into_val(env),
+            ArgSpec::To => values
+                .to
+                .expect("`To` is only valid for mint")
+                .into_val(env),
+            ArgSpec::Amount => values
+                .amount
+                .expect("`Amount` is only valid for mint")
+                .into_val(env),
+            ArgSpec::Bool(v) => v.into_val(env),
+            ArgSpec::U32(v) => v.into_val(env),
+            ArgSpec::U64(v) => v.into_val(env),
+            ArgSpec::I64(v) => v.into_val(env),
+            ArgSpec::I128(v) => v.into_val(env),
+            ArgSpec::String(v) => String::from_str(env, v).into_val(env),
+            ArgSpec::Symbol(v) => Symbol::new(env, v).into_val(env),
+            ArgSpec::Bytes(v) => Bytes::from_slice(env, v).into_val(env),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_sdk::testutils::{Address as _, Ledger};
+
+    /// A contract that stores an address in its instance storage:
+    ///
+    /// ```text
+    /// (module
+    ///   (import "l" "_" (func $put_contract_data (param i64 i64 i64) (result i64)))
+    ///   (import "l" "1" (func $get_contract_data (param i64 i64) (result i64)))
+    ///   (func (export "init") (param $admin i64) (result i64)
+    ///     (drop (call $put_contract_data (i64.const 4) (local.get $admin) (i64.const 2)))
+    ///     (i64.const 2))
+    ///   (func (export "admin") (result i64)
+    ///     (call $get_contract_data (i64.const 4) (i64.const 2))))
+    /// ```
+    ///
+    /// The key is `0u32`, the storage type is instance, and `init` returns void.
+    #[rustfmt::skip]
+    const STORAGE_WASM: &[u8] = &[
+        // magic, version
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // type section
+        0x01, 0x17, 0x04,
+        0x60, 0x03, 0x7e, 0x7e, 0x7e, 0x01, 0x7e,
+        0x60, 0x02, 0x7e, 0x7e, 0x01, 0x7e,
+        0x60, 0x01, 0x7e, 0x01, 0x7e,
+        0x60, 0x00, 0x01, 0x7e,
+        // import section
+        0x02, 0x0d, 0x02,
+        0x01, b'l', 0x01, b'_', 0x00, 0x00,
+        0x01, b'l', 0x01, b'1', 0x00, 0x01,
+        // function section
+        0x03, 0x03, 0x02, 0x02, 0x03,
+        // export section
+        0x07, 0x10, 0x02,
+        0x04, b'i', b'n', b'i', b't', 0x00, 0x02,
+        0x05, b'a', b'd', b'm', b'i', b'n', 0x00, 0x03,
+        // code section
+        0x0a, 0x18, 0x02,
+        0x0d, 0x00, 0x42, 0x04, 0x20, 0x00, 0x42, 0x02, 0x10, 0x00, 0x1a, 0x42, 0x02, 0x0b,
+        0x08, 0x00, 0x42, 0x04, 0x42, 0x02, 0x10, 0x01, 0x0b,
+        // contractenvmetav0: the interface version for protocol 20
+        0x00, 0x1e,
+        0x11, b'c', b'o', b'n', b't', b'r', b'a', b'c', b't',
+        b'e', b'n', b'v', b'm', b'e', b't', b'a', b'v', b'0',
+        0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x14, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn wasm_state_survives_reregister() {
+        let config = Config::wasm(STORAGE_WASM, FnSpec::new("init", vec![ArgSpec::Admin]));
+
+        let env = Env::default();
+        let admin = Address::generate(&env);
+        let token_contract_id = config.register_contract_init(&env, &admin);
+
+        // Move to a new `Env`, as the fuzzer does between transactions.
+        let env = Env::from_snapshot(env.to_snapshot());
+        env.ledger().with_mut(|li| li.sequence_number += 1);
+        let token_contract_id = ScAddress::try_from(&token_contract_id).unwrap();
+        let token_contract_id = Address::try_from_val(&env, &token_contract_id).unwrap();
+        config.reregister_contract(&env, &token_contract_id);
+
+        let stored: Address = env.invoke_contract(
+            &token_contract_id,
+            &Symbol::new(&env, "admin"),
+            soroban_sdk::Vec::new(&env),
+        );
+        assert_eq!(
+            ScAddress::try_from(&stored).unwrap(),
+            ScAddress::try_from(&admin).unwrap()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "the token's instance doesn't run its WASM")]
+    fn reregister_catches_expired_instances() {
+        let config = Config::wasm(STORAGE_WASM, FnSpec::new("init", vec![ArgSpec::Admin]));
+
+        let env = Env::default();
+        let admin = Address::generate(&env);
+        let token_contract_id = config.register_contract_init(&env, &admin);
+
+        // Drop the instance, as purging it once it expires does.
+        let mut snapshot = env.to_snapshot();
+        snapshot.ledger.ledger_entries.retain(|(key, _)| {
+            !matches!(
+                key.as_ref(),
+                LedgerKey::ContractData(LedgerKeyContractData {
+                    key: ScVal::LedgerKeyContractInstance,
+                    ..
+                })
+            )
+        });
+        let env = Env::from_snapshot(snapshot);
+        let token_contract_id = ScAddress::try_from(&token_contract_id).unwrap();
+        let token_contract_id = Address::try_from_val(&env, &token_contract_id).unwrap();
+        config.reregister_contract(&env, &token_contract_id);
+    }
+
+    #[test]
+    fn extension_commands_run_undecodable_payloads() {
+        let ran = Rc::new(std::cell::RefCell::new(RustVec::new()));
+        let command = ExtensionCommand::new("record", {
+            let ran = ran.clone();
+            move |_: &Env, _: &Address, _: &[TestSigner], _: &mut dyn TokenModel, s: RustString| {
+                ran.borrow_mut().push(s);
+            }
+        });
+        let config = Config::wasm(STORAGE_WASM, FnSpec::new("init", vec![ArgSpec::Admin]))
+            .with_extension_command(command);
+
+        let env = Env::default();
+        let token_contract_id = Address::generate(&env);
+        let mut model = ContractState::init();
+        for command in config.extension_commands() {
+            // Not UTF-8, so not a `String` with `arbitrary_take_rest`.
+            command.exec(&env, &token_contract_id, &[], &mut model, &[0xff, 0xfe]);
+            command.exec(&env, &token_contract_id, &[], &mut model, b"abc");
+        }
+
+        assert_eq!(*ran.borrow(), ["", "abc"]);
+    }
+}
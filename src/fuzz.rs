@@ -1,6 +1,9 @@
-use crate::addrgen::{AddressGenerator, TestSigner};
+use crate::accounts::{sign_payload_for_account, AccountEd25519Signature};
+use crate::addrgen::{AddressGenerator, AddressType, TestSigner};
 use crate::config::*;
 use crate::input::*;
+use crate::model::*;
+use crate::testgen::{RecordedCall, Recording};
 use crate::util::*;
 use crate::DAY_IN_LEDGERS;
 use ed25519_dalek::{Signer, SigningKey};
@@ -8,28 +11,29 @@ use itertools::Itertools;
 use libfuzzer_sys::Corpus;
 use num_bigint::BigInt;
 use sha2::{Digest, Sha256};
-use soroban_sdk::testutils::{Address as _, Events, Ledger, LedgerInfo};
 use soroban_sdk::testutils::Snapshot;
+use soroban_sdk::testutils::{
+    Address as _, AuthorizedFunction, AuthorizedInvocation, ContractFunctionSet, Events, Ledger,
+    LedgerInfo,
+};
 use soroban_sdk::xdr::{
-    HashIdPreimage, HashIdPreimageSorobanAuthorization, InvokeContractArgs, ScAddress, ScSymbol,
-    ScVal, SorobanAddressCredentials, SorobanAuthorizationEntry, SorobanAuthorizedFunction,
+    ContractEventBody, ContractEventType, HashIdPreimage, HashIdPreimageSorobanAuthorization,
+    Int128Parts, InvokeContractArgs, LedgerKey, LedgerKeyContractData, ScAddress, ScSymbol, ScVal,
+    SorobanAddressCredentials, SorobanAuthorizationEntry, SorobanAuthorizedFunction,
     SorobanAuthorizedInvocation, SorobanCredentials, VecM,
 };
 use soroban_sdk::xdr::{Limited, Limits, WriteXdr};
 use soroban_sdk::xdr::{ScErrorCode, ScErrorType};
 use soroban_sdk::{
-    contract, contractimpl, contracttype, token::Client, Address, Bytes, BytesN, Env, Error,
-    IntoVal, InvokeError, TryFromVal, Val,
+    token::Client, Address, Bytes, BytesN, ConversionError, Env, Error, IntoVal, InvokeError,
+    Symbol, TryFromVal, Val,
 };
-use std::collections::BTreeMap;
+use std::string::String as RustString;
 use std::vec::Vec as RustVec;
 
 // Don't know where this number comes from.
 const MAX_LEDGERS_TO_ADVANCE: u32 = 4095;
 
-type TokenContractResult =
-    Result<Result<(), <() as TryFromVal<Env, Val>>::Error>, Result<Error, InvokeError>>;
-
 pub fn fuzz_token(config: Config, input: Input) -> Corpus {
     if input.transactions.iter().all(|tx| tx.commands.is_empty()) {
         return Corpus::Reject;
@@ -37,487 +41,777 @@ pub fn fuzz_token(config: Config, input: Input) -> Corpus {
 
     //eprintln!("input: {input:#?}");
 
-    // The initial Env. This will be destroyed and recreated when we advance time,
-    // to simulate distinct transactions.
-    let mut env = Env::default();
-
-    let token_contract_id_bytes: RustVec<u8>;
-
-    // Do initial setup, including registering the contract.
-    {
-        input.address_generator.setup_account_storage(&env);
-
-        let signers = input.address_generator.generate_signers(&env);
-        let admin = &signers[0].address;
+    let mut run = TokenRun::new(config, &input);
+    run.exec_input(&input);
 
-        let token_contract_id = config.register_contract_init(&env, admin);
-        token_contract_id_bytes = address_to_bytes(&token_contract_id);
-    }
+    Corpus::Keep
+}
 
-    let mut contract_state = ContractState::init();
-    let mut current_state = CurrentState::new(
-        &env,
-        &config,
-        &token_contract_id_bytes,
-        &input.address_generator,
+/// Run the input as [`fuzz_token`] does,
+/// recording what it does for [`generate_test`](crate::generate_test).
+pub(crate) fn record_token(config: Config, input: &Input, recording: &Recording) {
+    let mut run = TokenRun::new(config, input);
+    recording.setup(
+        &run.current_state.token_client.address,
+        &run.current_state.proxy,
+        &run.current_state.accounts,
     );
-    let mut signature_nonce = 0;
+    run.recording = Some(recording.clone());
+    run.exec_input(input);
+}
 
-    // Save some values that should never change
-    // fixme put this in the ContractState ctor
-    {
-        let token_client = &current_state.token_client;
+/// Run the same input against several tokens in lockstep,
+/// panicking at the first command where they behave differently.
+///
+/// After every command the result of each call,
+/// the events it emitted, and the balances and allowances
+/// of all accounts are compared to those of the first config.
+/// Each token is also still checked against its own model,
+/// as in [`fuzz_token`].
+pub fn fuzz_token_differential(configs: Vec<Config>, input: Input) -> Corpus {
+    assert!(!configs.is_empty(), "no configs to compare");
+    assert!(
+        configs
+            .iter()
+            .all(|config| config.number_of_addresses() == configs[0].number_of_addresses()),
+        "configs have different numbers of addresses"
+    );
 
-        contract_state.name = string_to_bytes(token_client.name());
-        contract_state.symbol = string_to_bytes(token_client.symbol());
-        contract_state.decimals = token_client.decimals();
+    if input.transactions.iter().all(|tx| tx.commands.is_empty()) {
+        return Corpus::Reject;
     }
 
-    for transaction in &input.transactions {
-        // The Env will be different for each tx, so we need to reconstruct
-        // everything that depends on it.
-        env.budget().reset_unlimited();
-
-        for command in &transaction.commands {
-            // println!("------- command: {:#?}", command);
-            exec_command(
-                &command,
-                &env,
-                &token_contract_id_bytes,
-                &mut contract_state,
-                &current_state,
-                &mut signature_nonce,
-            );
-        }
-
-        // Advance time and begin new transaction
-        {
-            env = advance_time(
-                &config,
-                env,
-                &token_contract_id_bytes,
-                transaction.advance_ledgers,
-            );
-            // NB: This env is reconstructed and all previous env-based objects are invalid
+    let mut runs: RustVec<TokenRun> = configs
+        .into_iter()
+        .map(|config| TokenRun::new(config, &input))
+        .collect();
 
-            current_state = CurrentState::new(
-                &env,
-                &config,
-                &token_contract_id_bytes,
-                &input.address_generator,
-            );
+    for (tx_index, transaction) in input.transactions.iter().enumerate() {
+        for run in &mut runs {
+            run.begin_transaction();
+        }
 
-            // update saved allowance number after advance ledgers
-            // fixme track expiration ledger instead of asking the contract
-            {
-                let pairs = current_state
-                    .accounts
-                    .iter()
-                    .cartesian_product(current_state.accounts.iter());
-                for (signer1, signer2) in pairs {
-                    let expected_allowance =
-                        contract_state.get_allowance(&signer1.address, &signer2.address);
-                    let actual_allowance = current_state
-                        .token_client
-                        .allowance(&signer1.address, &signer2.address);
-                    if actual_allowance == 0 && expected_allowance != 0 {
-                        // Assume the allowance expired.
-                        contract_state.set_allowance(
-                            &signer1.address,
-                            &signer2.address,
-                            actual_allowance,
-                        );
-                    }
+        for (command_index, command) in transaction.commands.iter().enumerate() {
+            let outcomes: RustVec<CommandOutcome> = runs
+                .iter_mut()
+                .map(|run| {
+                    let calls = run.exec_command(command);
+                    run.observe(calls)
+                })
+                .collect();
+
+            let (expected, others) = outcomes.split_first().expect("configs");
+            for (i, actual) in others.iter().enumerate() {
+                if actual != expected {
+                    panic!(
+                        "config {} diverged from config 0 \
+                         at transaction {tx_index}, command {command_index}\n\
+                         command: {command:#?}\n\
+                         config 0: {expected:#?}\n\
+                         config {}: {actual:#?}",
+                        i + 1,
+                        i + 1,
+                    );
                 }
             }
+        }
 
-            assert_state(&contract_state, &current_state);
+        for run in &mut runs {
+            run.end_transaction(transaction.advance_ledgers, transaction.switch_network);
         }
     }
 
     Corpus::Keep
 }
 
-fn exec_command(
-    command: &Command,
-    env: &Env,
-    token_contract_id_bytes: &[u8],
-    contract_state: &mut ContractState,
-    current_state: &CurrentState,
-    signature_nonce: &mut i64,
-) {
-    let admin_client = &current_state.admin_client;
-    let token_client = &current_state.token_client;
-    let accounts = &current_state.accounts;
-
-    match command {
-        Command::Mint(input) => {
-            mock_auths_for_command(
-                env,
-                "mint",
-                &input.auths,
-                current_state,
-                token_contract_id_bytes,
-                signature_nonce,
-                (&accounts[input.to_account_index].address, input.amount).into_val(env),
-            );
+/// The state of fuzzing a single token through all the transactions of an input.
+struct TokenRun {
+    config: Config,
+    address_generator: AddressGenerator,
+    /// The types of the addresses,
+    /// including those introduced by `Command::FreshAddress`.
+    address_types: RustVec<AddressType>,
+    // The Env. This will be destroyed and recreated when we advance time,
+    // to simulate distinct transactions.
+    env: Env,
+    token_contract_id_bytes: RustVec<u8>,
+    proxy_contract_id_bytes: RustVec<u8>,
+    model: Box<dyn TokenModel>,
+    metadata: Metadata,
+    current_state: CurrentState<'static>,
+    signature_nonce: i64,
+    /// The entries that authorized successful calls,
+    /// for replaying.
+    accepted_auths: RustVec<AcceptedAuth>,
+    extension_commands: RustVec<ExtensionCommand>,
+    /// How many times the ledger moved to a new network.
+    network_switches: u32,
+    /// Records the run for `generate_test`.
+    /// `fuzz_token` leaves this as `None`.
+    recording: Option<Recording>,
+}
 
-            let r = admin_client.try_mint(&accounts[input.to_account_index].address, &input.amount);
+impl TokenRun {
+    fn new(config: Config, input: &Input) -> Self {
+        let env = Env::default();
 
-            verify_token_contract_result(&env, &r);
+        let token_contract_id_bytes: RustVec<u8>;
+        let address_types = input
+            .address_generator
+            .initial_address_types(config.number_of_addresses());
 
-            if input.amount < 0 {
-                assert!(r.is_err());
+        // Do initial setup, including registering the contract.
+        {
+            let signers = input
+                .address_generator
+                .generate_signers(&env, &address_types);
+            for signer in &signers {
+                signer.setup_account_storage(&env);
             }
+            let admin = &signers[0].address;
 
-            if input.auths[0] == false {
-                assert!(r.is_err());
-            }
+            let token_contract_id = config.register_contract_init(&env, admin);
+            token_contract_id_bytes = address_to_bytes(&token_contract_id);
+        }
+
+        let proxy_contract_id = env.register_contract(None, ProxyContract);
+        let proxy_contract_id_bytes = address_to_bytes(&proxy_contract_id);
+
+        let model = config.new_model();
+        let current_state = CurrentState::new(
+            &env,
+            &config,
+            &token_contract_id_bytes,
+            &proxy_contract_id_bytes,
+            &input.address_generator,
+            &address_types,
+        );
 
-            if let Ok(r) = r {
-                let _r = r.expect("ok");
+        // Save some values that should never change
+        let metadata = {
+            let token_client = &current_state.token_client;
 
-                contract_state.add_balance(&accounts[input.to_account_index].address, input.amount);
-                contract_state.sum_of_mints =
-                    contract_state.sum_of_mints.clone() + BigInt::from(input.amount);
+            Metadata {
+                name: string_to_bytes(token_client.name()),
+                symbol: string_to_bytes(token_client.symbol()),
+                decimals: token_client.decimals(),
             }
+        };
+
+        let extension_commands = config.extension_commands();
+
+        TokenRun {
+            config,
+            address_generator: input.address_generator.clone(),
+            address_types,
+            env,
+            token_contract_id_bytes,
+            proxy_contract_id_bytes,
+            model,
+            metadata,
+            current_state,
+            signature_nonce: 0,
+            accepted_auths: RustVec::new(),
+            extension_commands,
+            network_switches: 0,
+            recording: None,
         }
-        Command::Approve(input) => {
-            mock_auths_for_command(
-                env,
-                "approve",
-                &input.auths,
-                current_state,
-                token_contract_id_bytes,
-                signature_nonce,
-                (
-                    &accounts[input.from_account_index].address,
-                    &accounts[input.spender_account_index].address,
-                    input.amount,
-                    input.expiration_ledger,
-                )
-                    .into_val(env),
-            );
-
-            let r = token_client.try_approve(
-                &accounts[input.from_account_index].address,
-                &accounts[input.spender_account_index].address,
-                &input.amount,
-                &input.expiration_ledger,
-            );
+    }
 
-            verify_token_contract_result(&env, &r);
+    fn exec_input(&mut self, input: &Input) {
+        for transaction in &input.transactions {
+            self.begin_transaction();
 
-            if input.amount < 0 {
-                assert!(r.is_err());
+            for command in &transaction.commands {
+                // println!("------- command: {:#?}", command);
+                self.exec_command(command);
             }
 
-            if input.auths[input.from_account_index] == false {
-                assert!(r.is_err());
-            }
+            self.end_transaction(transaction.advance_ledgers, transaction.switch_network);
+        }
+    }
 
-            if let Ok(r) = r {
-                let _r = r.expect("ok");
+    fn begin_transaction(&mut self) {
+        self.env.budget().reset_unlimited();
+    }
 
-                contract_state.set_allowance(
-                    &accounts[input.from_account_index].address,
-                    &accounts[input.spender_account_index].address,
-                    input.amount,
-                );
+    /// Advance time and check the token against the model.
+    fn end_transaction(&mut self, advance_ledgers: u32, switch_network: bool) {
+        let network_passphrase = switch_network.then(|| {
+            self.network_switches += 1;
+            format!("Soroban Token Fuzzer Network ; {}", self.network_switches)
+        });
+        let network_id = network_passphrase.as_deref().map(passphrase_network_id);
+
+        let env = std::mem::take(&mut self.env);
+        self.env = advance_time(
+            env,
+            &self.token_contract_id_bytes,
+            advance_ledgers,
+            network_id,
+            |env, token_contract_id| self.config.reregister_contract(env, token_contract_id),
+        );
+        // NB: This env is reconstructed and all previous env-based objects are invalid
+
+        // The Env will be different for each tx, so we need to reconstruct
+        // everything that depends on it.
+        self.current_state = CurrentState::new(
+            &self.env,
+            &self.config,
+            &self.token_contract_id_bytes,
+            &self.proxy_contract_id_bytes,
+            &self.address_generator,
+            &self.address_types,
+        );
+
+        // update saved allowance number after advance ledgers
+        // fixme track expiration ledger instead of asking the contract
+        {
+            let current_state = &self.current_state;
+            let pairs = current_state
+                .accounts
+                .iter()
+                .cartesian_product(current_state.accounts.iter());
+            for (signer1, signer2) in pairs {
+                let expected_allowance = self.model.allowance(&signer1.address, &signer2.address);
+                let actual_allowance = current_state
+                    .token_client
+                    .allowance(&signer1.address, &signer2.address);
+                if actual_allowance == 0 && expected_allowance != 0 {
+                    // Assume the allowance expired.
+                    self.model
+                        .expire_allowance(&signer1.address, &signer2.address);
+                }
             }
         }
-        Command::TransferFrom(input) => {
-            mock_auths_for_command(
-                env,
-                "transfer_from",
-                &input.auths,
-                current_state,
-                token_contract_id_bytes,
-                signature_nonce,
-                (
-                    &accounts[input.spender_account_index].address,
-                    &accounts[input.from_account_index].address,
-                    &accounts[input.to_account_index].address,
-                    input.amount,
-                )
-                    .into_val(env),
-            );
 
-            let r = token_client.try_transfer_from(
-                &accounts[input.spender_account_index].address,
-                &accounts[input.from_account_index].address,
-                &accounts[input.to_account_index].address,
-                &input.amount,
+        if let Some(recording) = &self.recording {
+            recording.end_transaction(
+                advance_ledgers,
+                network_passphrase.as_deref(),
+                self.model.as_ref(),
+                &self.current_state.accounts,
             );
+        }
 
-            verify_token_contract_result(&env, &r);
+        assert_state(self.model.as_ref(), &self.metadata, &self.current_state);
+        assert_invariants(
+            &self.config,
+            &self.env,
+            self.model.as_ref(),
+            &self.current_state,
+        );
+    }
 
-            if input.amount < 0 {
-                assert!(r.is_err());
-            }
+    /// Resolve a command's amount against the model,
+    /// from the balance of `account` and the allowance of `spender`.
+    fn resolve_amount(
+        &self,
+        amount: &Amount,
+        account: &Address,
+        spender: Option<&Address>,
+    ) -> i128 {
+        let balance = self.model.balance(account);
+        let allowance = spender.map_or(0, |spender| self.model.allowance(account, spender));
+        amount.resolve(balance, allowance)
+    }
 
-            if input.auths[input.spender_account_index] == false {
-                assert!(r.is_err());
-            }
+    /// Sign a call to the token as `call.auth` says, make it,
+    /// and check the result.
+    ///
+    /// `failure` has the failures the call's arguments predict;
+    /// whether it is authorized is filled in here.
+    /// If the call succeeds, `update_model` applies it to the model.
+    fn exec_call(
+        &mut self,
+        call: TokenCall,
+        failure: ExpectedFailure,
+        events_before: usize,
+        update_model: impl FnOnce(&mut dyn TokenModel),
+    ) -> CallOutcome {
+        let env = &self.env;
+        let current_state = &self.current_state;
+        let token_client = &current_state.token_client;
+        let signer = &current_state.accounts[call.signer].address;
+
+        let auths = mock_auths_for_command(
+            env,
+            &CommandAuths {
+                fn_name: call.fn_name,
+                args: call.args.clone(),
+                required_signer: call.signer,
+                auth: call.auth,
+            },
+            current_state,
+            &self.token_contract_id_bytes,
+            &mut self.signature_nonce,
+            &self.accepted_auths,
+        );
 
-            if let Ok(r) = r {
-                let _r = r.expect("ok");
+        let r = if call.auth.via_proxy {
+            env.set_auths(&auths.entries);
+            current_state.forward(signer, call.fn_name, call.args.clone())
+        } else if let Some((to_account_index, amount)) = call.mint {
+            current_state
+                .admin_client
+                .set_auths(&auths.entries)
+                .try_mint(&current_state.accounts[to_account_index].address, &amount)
+        } else {
+            env.set_auths(&auths.entries);
+            env.try_invoke_contract::<(), Error>(
+                &token_client.address,
+                &Symbol::new(env, call.fn_name),
+                call.args.clone(),
+            )
+        };
+
+        let outcome = CallOutcome::new(env, call.fn_name, &r, events_before);
+
+        let failure = ExpectedFailure {
+            unauthorized: !auths.authorized,
+            via_proxy: call.auth.via_proxy,
+            ..failure
+        };
+        self.check_call(
+            &RecordedCall {
+                fn_name: call.fn_name,
+                args: &call.args,
+                signer,
+                via_proxy: call.auth.via_proxy,
+                auths: &auths.entries,
+                result: &r,
+            },
+            &failure,
+        );
 
-                contract_state
-                    .sub_balance(&accounts[input.from_account_index].address, input.amount);
-                contract_state.add_balance(&accounts[input.to_account_index].address, input.amount);
+        if let Ok(r) = r {
+            r.expect("ok");
 
-                contract_state.sub_allowance(
-                    &accounts[input.from_account_index].address,
-                    &accounts[input.spender_account_index].address,
-                    input.amount,
-                );
-            }
-        }
-        Command::Transfer(input) => {
-            mock_auths_for_command(
+            assert_auths(
                 env,
-                "transfer",
-                &input.auths,
-                current_state,
-                token_contract_id_bytes,
-                signature_nonce,
-                (
-                    &accounts[input.from_account_index].address,
-                    &accounts[input.to_account_index].address,
-                    input.amount,
-                )
-                    .into_val(env),
+                signer,
+                &token_client.address,
+                call.fn_name,
+                call.args,
+                call.auth.via_proxy.then_some(&current_state.proxy),
             );
 
-            let r = token_client.try_transfer(
-                &accounts[input.from_account_index].address,
-                &accounts[input.to_account_index].address,
-                &input.amount,
-            );
+            self.accepted_auths.extend(auths.required_entry);
 
-            verify_token_contract_result(&env, &r);
+            update_model(self.model.as_mut());
+        }
 
-            if input.amount < 0 {
-                assert!(r.is_err());
-            }
+        outcome
+    }
 
-            if input.auths[input.from_account_index] == false {
-                assert!(r.is_err());
-            }
+    /// Check the result of a call against the failure the model predicts.
+    ///
+    /// This is where calls are recorded for `generate_test`,
+    /// before the checks, so that the test ends with the call that failed.
+    fn check_call(&self, call: &RecordedCall, failure: &ExpectedFailure) {
+        let error_codes = self.config.error_codes();
+        if let Some(recording) = &self.recording {
+            recording.call(
+                call,
+                expected_error(error_codes, failure),
+                &self.current_state.accounts,
+            );
+        }
 
-            if let Ok(r) = r {
-                let _r = r.expect("ok");
+        verify_token_contract_result(&self.env, call.result);
 
-                contract_state
-                    .sub_balance(&accounts[input.from_account_index].address, input.amount);
-                contract_state.add_balance(&accounts[input.to_account_index].address, input.amount);
-            }
+        assert_expected_error(&self.env, error_codes, failure, call.result);
+    }
+
+    /// Query the token for everything a command might have changed.
+    fn observe(&self, calls: RustVec<CallOutcome>) -> CommandOutcome {
+        let accounts = &self.current_state.accounts;
+        let token_client = &self.current_state.token_client;
+
+        CommandOutcome {
+            calls,
+            balances: accounts
+                .iter()
+                .map(|signer| token_client.balance(&signer.address))
+                .collect(),
+            allowances: accounts
+                .iter()
+                .cartesian_product(accounts.iter())
+                .map(|(signer1, signer2)| {
+                    token_client.allowance(&signer1.address, &signer2.address)
+                })
+                .collect(),
         }
-        Command::BurnFrom(input) => {
-            mock_auths_for_command(
-                env,
-                "burn_from",
-                &input.auths,
-                current_state,
-                token_contract_id_bytes,
-                signature_nonce,
-                (
-                    &accounts[input.spender_account_index].address,
-                    &accounts[input.from_account_index].address,
-                    input.amount,
-                )
-                    .into_val(env),
-            );
+    }
 
-            let r = token_client.try_burn_from(
-                &accounts[input.spender_account_index].address,
-                &accounts[input.from_account_index].address,
-                &input.amount,
-            );
+    /// Execute a command, returning the outcome of each call it made to the token.
+    fn exec_command(&mut self, command: &Command) -> RustVec<CallOutcome> {
+        let env = &self.env;
+        let current_state = &self.current_state;
+        let token_client = &current_state.token_client;
+        let accounts = &current_state.accounts;
+
+        let mut command = command.clone();
+        command.wrap_account_indexes(accounts.len());
+        let command = &command;
+
+        let events_before = env.host().get_events().unwrap().0.len();
+
+        match command {
+            Command::Mint(input) => {
+                let to = accounts[input.to_account_index].address.clone();
+                let amount = self.resolve_amount(&input.amount, &to, None);
+                let mint_spec = self.config.mint_spec();
+                let args = mint_spec.args(
+                    env,
+                    &ArgValues {
+                        admin: &accounts[0].address,
+                        to: Some(&to),
+                        amount: Some(amount),
+                    },
+                );
 
-            verify_token_contract_result(&env, &r);
+                let outcome = self.exec_call(
+                    TokenCall {
+                        fn_name: &mint_spec.fn_name,
+                        args,
+                        signer: 0,
+                        auth: &input.auth,
+                        mint: Some((input.to_account_index, amount)),
+                    },
+                    ExpectedFailure {
+                        negative_amount: amount < 0,
+                        ..Default::default()
+                    },
+                    events_before,
+                    |model| model.mint(&to, amount),
+                );
 
-            if input.amount < 0 {
-                assert!(r.is_err());
+                vec![outcome]
             }
+            Command::Approve(input) => {
+                let from = accounts[input.from_account_index].address.clone();
+                let spender = accounts[input.spender_account_index].address.clone();
+                let amount = self.resolve_amount(&input.amount, &from, Some(&spender));
+                let args = (&from, &spender, amount, input.expiration_ledger).into_val(env);
+
+                let outcome = self.exec_call(
+                    TokenCall {
+                        fn_name: "approve",
+                        args,
+                        signer: input.from_account_index,
+                        auth: &input.auth,
+                        mint: None,
+                    },
+                    ExpectedFailure {
+                        negative_amount: amount < 0,
+                        ..Default::default()
+                    },
+                    events_before,
+                    |model| model.approve(&from, &spender, amount, input.expiration_ledger),
+                );
 
-            if input.auths[input.spender_account_index] == false {
-                assert!(r.is_err());
+                vec![outcome]
             }
+            Command::TransferFrom(input) => {
+                let spender = accounts[input.spender_account_index].address.clone();
+                let from = accounts[input.from_account_index].address.clone();
+                let to = accounts[input.to_account_index].address.clone();
+                let amount = self.resolve_amount(&input.amount, &from, Some(&spender));
+                let args = (&spender, &from, &to, amount).into_val(env);
+
+                let outcome = self.exec_call(
+                    TokenCall {
+                        fn_name: "transfer_from",
+                        args,
+                        signer: input.spender_account_index,
+                        auth: &input.auth,
+                        mint: None,
+                    },
+                    ExpectedFailure {
+                        negative_amount: amount < 0,
+                        insufficient_allowance: self.model.allowance(&from, &spender) < amount,
+                        insufficient_balance: self.model.balance(&from) < amount,
+                        ..Default::default()
+                    },
+                    events_before,
+                    |model| model.transfer_from(&spender, &from, &to, amount),
+                );
 
-            if let Ok(r) = r {
-                let _r = r.expect("ok");
+                vec![outcome]
+            }
+            Command::Transfer(input) => {
+                let from = accounts[input.from_account_index].address.clone();
+                let to = accounts[input.to_account_index].address.clone();
+                let amount = self.resolve_amount(&input.amount, &from, None);
+                let args = (&from, &to, amount).into_val(env);
+
+                let outcome = self.exec_call(
+                    TokenCall {
+                        fn_name: "transfer",
+                        args,
+                        signer: input.from_account_index,
+                        auth: &input.auth,
+                        mint: None,
+                    },
+                    ExpectedFailure {
+                        negative_amount: amount < 0,
+                        insufficient_balance: self.model.balance(&from) < amount,
+                        ..Default::default()
+                    },
+                    events_before,
+                    |model| model.transfer(&from, &to, amount),
+                );
 
-                contract_state
-                    .sub_balance(&accounts[input.from_account_index].address, input.amount);
+                vec![outcome]
+            }
+            Command::BurnFrom(input) => {
+                let spender = accounts[input.spender_account_index].address.clone();
+                let from = accounts[input.from_account_index].address.clone();
+                let amount = self.resolve_amount(&input.amount, &from, Some(&spender));
+                let args = (&spender, &from, amount).into_val(env);
+
+                let outcome = self.exec_call(
+                    TokenCall {
+                        fn_name: "burn_from",
+                        args,
+                        signer: input.spender_account_index,
+                        auth: &input.auth,
+                        mint: None,
+                    },
+                    ExpectedFailure {
+                        negative_amount: amount < 0,
+                        insufficient_allowance: self.model.allowance(&from, &spender) < amount,
+                        insufficient_balance: self.model.balance(&from) < amount,
+                        ..Default::default()
+                    },
+                    events_before,
+                    |model| model.burn_from(&spender, &from, amount),
+                );
+
+                vec![outcome]
+            }
+            Command::Burn(input) => {
+                let from = accounts[input.from_account_index].address.clone();
+                let amount = self.resolve_amount(&input.amount, &from, None);
+                let args = (&from, amount).into_val(env);
+
+                let outcome = self.exec_call(
+                    TokenCall {
+                        fn_name: "burn",
+                        args,
+                        signer: input.from_account_index,
+                        auth: &input.auth,
+                        mint: None,
+                    },
+                    ExpectedFailure {
+                        negative_amount: amount < 0,
+                        insufficient_balance: self.model.balance(&from) < amount,
+                        ..Default::default()
+                    },
+                    events_before,
+                    |model| model.burn(&from, amount),
+                );
 
-                contract_state.sub_allowance(
+                vec![outcome]
+            }
+            Command::ApproveAndTransferFrom(input) => {
+                // Resolve the amount once, so that the approval
+                // doesn't change the amount transferred.
+                let amount = self.resolve_amount(
+                    &input.amount,
+                    &accounts[input.from_account_index].address,
+                    Some(&accounts[input.spender_account_index].address),
+                );
+                let mut outcomes =
+                    self.exec_command(&Command::Approve(input.to_approve_input(amount)));
+                outcomes.extend(
+                    self.exec_command(&Command::TransferFrom(input.to_transfer_from_input(amount))),
+                );
+                outcomes
+            }
+            Command::ApproveAndBurnFrom(input) => {
+                let amount = self.resolve_amount(
+                    &input.amount,
                     &accounts[input.from_account_index].address,
-                    &accounts[input.spender_account_index].address,
-                    input.amount,
+                    Some(&accounts[input.spender_account_index].address),
+                );
+                let mut outcomes =
+                    self.exec_command(&Command::Approve(input.to_approve_input(amount)));
+                outcomes.extend(
+                    self.exec_command(&Command::BurnFrom(input.to_burn_from_input(amount))),
+                );
+                outcomes
+            }
+            Command::Extension(input) => {
+                let num_commands = self.extension_commands.len();
+                if num_commands == 0 {
+                    return vec![];
+                }
+
+                let command = &self.extension_commands[input.command_index as usize % num_commands];
+                if let Some(recording) = &self.recording {
+                    recording.comment(&format!(
+                        "the extension command `{}` ran here",
+                        command.name()
+                    ));
+                }
+                command.exec(
+                    env,
+                    &token_client.address,
+                    accounts,
+                    self.model.as_mut(),
+                    &input.payload,
                 );
 
-                contract_state.sum_of_burns =
-                    contract_state.sum_of_burns.clone() + &BigInt::from(input.amount);
+                vec![]
             }
-        }
-        Command::Burn(input) => {
-            mock_auths_for_command(
-                env,
-                "burn",
-                &input.auths,
-                current_state,
-                token_contract_id_bytes,
-                signature_nonce,
-                (&accounts[input.from_account_index].address, input.amount).into_val(env),
-            );
+            Command::ReplayAuth(input) => {
+                // Once the ledger moves to another network,
+                // entries without signatures over the payload are valid again there.
+                let network_id = network_id(env);
+                let replayable: RustVec<_> = self
+                    .accepted_auths
+                    .iter()
+                    .filter(|accepted| {
+                        accepted.network_id == network_id
+                            || accepted.credentials.signature != ScVal::Void
+                    })
+                    .collect();
+                let num_accepted = replayable.len();
+                if num_accepted == 0 {
+                    return vec![];
+                }
 
-            let r =
-                token_client.try_burn(&accounts[input.from_account_index].address, &input.amount);
+                let accepted = replayable[input.accepted_index as usize % num_accepted];
+                let entry = accepted.entry();
+                let function = accepted.call.root_function();
+                let fn_name =
+                    Symbol::try_from_val(env, &ScVal::Symbol(function.function_name.clone()))
+                        .unwrap();
+                let args = soroban_sdk::Vec::<Val>::try_from_val(
+                    env,
+                    &ScVal::Vec(Some(function.args.clone().into())),
+                )
+                .unwrap();
 
-            verify_token_contract_result(&env, &r);
+                env.set_auths(std::slice::from_ref(&entry));
 
-            if input.amount < 0 {
-                assert!(r.is_err());
-            }
+                let contract_id =
+                    Address::try_from_val(env, &ScVal::Address(function.contract_address.clone()))
+                        .unwrap();
+                let r = env.try_invoke_contract::<(), Error>(&contract_id, &fn_name, args.clone());
 
-            if input.auths[input.from_account_index] == false {
-                assert!(r.is_err());
+                let outcome = CallOutcome::new(
+                    env,
+                    &function.function_name.to_utf8_string_lossy(),
+                    &r,
+                    events_before,
+                );
+
+                // The nonce was consumed by the accepted call,
+                // or the entry was signed for another network.
+                let failure = ExpectedFailure {
+                    unauthorized: true,
+                    ..Default::default()
+                };
+                let token_function = &accepted.call.token_function;
+                self.check_call(
+                    &RecordedCall {
+                        fn_name: &token_function.function_name.to_utf8_string_lossy(),
+                        args: &soroban_sdk::Vec::try_from_val(
+                            env,
+                            &ScVal::Vec(Some(token_function.args.clone().into())),
+                        )
+                        .unwrap(),
+                        signer: &Address::try_from_val(
+                            env,
+                            &ScVal::Address(accepted.credentials.address.clone()),
+                        )
+                        .unwrap(),
+                        via_proxy: accepted.call.proxy_function.is_some(),
+                        auths: std::slice::from_ref(&entry),
+                        result: &r,
+                    },
+                    &failure,
+                );
+
+                vec![outcome]
             }
+            Command::FreshAddress(input) => {
+                let index = self.address_types.len();
+                if index == MAX_NUMBER_OF_ADDRESSES {
+                    return vec![];
+                }
 
-            if let Ok(r) = r {
-                let _r = r.expect("ok");
+                let signer =
+                    self.address_generator
+                        .generate_signer(&self.env, index, &input.address_type);
+                signer.setup_account_storage(&self.env);
 
-                contract_state
-                    .sub_balance(&accounts[input.from_account_index].address, input.amount);
+                self.address_types.push(input.address_type.clone());
+                self.current_state.accounts.push(signer);
 
-                contract_state.sum_of_burns =
-                    contract_state.sum_of_burns.clone() + &BigInt::from(input.amount);
+                vec![]
             }
         }
-        Command::ApproveAndTransferFrom(input) => {
-            exec_command(
-                &Command::Approve(input.to_approve_input()),
-                env,
-                token_contract_id_bytes,
-                contract_state,
-                current_state,
-                signature_nonce,
-            );
+    }
+}
 
-            exec_command(
-                &Command::TransferFrom(input.to_transfer_from_input()),
-                env,
-                token_contract_id_bytes,
-                contract_state,
-                current_state,
-                signature_nonce,
-            );
-        }
-        Command::ApproveAndBurnFrom(input) => {
-            exec_command(
-                &Command::Approve(input.to_approve_input()),
-                env,
-                token_contract_id_bytes,
-                contract_state,
-                current_state,
-                signature_nonce,
-            );
+/// What happened when a command was run against a token,
+/// for comparing tokens in [`fuzz_token_differential`].
+#[derive(Debug, PartialEq)]
+struct CommandOutcome {
+    calls: RustVec<CallOutcome>,
+    /// The balance of each account.
+    balances: RustVec<i128>,
+    /// The allowance of each (from, spender) pair of accounts.
+    allowances: RustVec<i128>,
+}
 
-            exec_command(
-                &Command::BurnFrom(input.to_burn_from_input()),
-                env,
-                token_contract_id_bytes,
-                contract_state,
-                current_state,
-                signature_nonce,
-            );
+/// The result of a single call to the token.
+#[derive(Debug, PartialEq)]
+struct CallOutcome {
+    fn_name: RustString,
+    result: Result<(), Result<Error, InvokeError>>,
+    /// The topics and data of the contract events emitted by the call.
+    ///
+    /// The contract id is left out so that different
+    /// deployments of the same token compare equal.
+    events: RustVec<(RustVec<ScVal>, ScVal)>,
+}
+
+impl CallOutcome {
+    fn new(env: &Env, fn_name: &str, r: &TokenContractResult, events_before: usize) -> Self {
+        let result = match r {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(_)) => Err(Ok(Error::from(ConversionError))),
+            Err(e) => Err(*e),
+        };
+
+        let events = env.host().get_events().unwrap().0;
+        let events = events[events_before..]
+            .iter()
+            .filter(|e| !e.failed_call && e.event.type_ == ContractEventType::Contract)
+            .map(|e| {
+                let ContractEventBody::V0(body) = &e.event.body;
+                (body.topics.to_vec(), body.data.clone())
+            })
+            .collect();
+
+        CallOutcome {
+            fn_name: fn_name.to_string(),
+            result,
+            events,
         }
     }
 }
 
-/// This tracks what we believe is true about the internal contract state.
-///
-/// We mirror calculations about balances etc that we expect the contract
-/// is making, which means we will be wrong if the token implements economics
-/// that differ from the example token.
-///
-/// This kind of state mirroring I would not generally do in a fuzz test
-/// but since the token interface is small and it can be used to test that
-/// multiple implementations behave in similar ways, I think it is worth
-/// the potential maintenance brittleness.
-///
-/// Since this state is persistent across transactions,
-/// it can not store anything containing an `Env`. Instead
-/// it can contain accessors that instantiate various contract
-/// types from any `Env`.
-pub struct ContractState {
+/// Token metadata that should never change.
+struct Metadata {
     name: RustVec<u8>,
     symbol: RustVec<u8>,
     decimals: u32,
-    balances: BTreeMap<RustVec<u8>, i128>,
-    allowances: BTreeMap<(RustVec<u8>, RustVec<u8>), i128>, // (from, spender)
-    sum_of_mints: BigInt,
-    sum_of_burns: BigInt,
-}
-
-impl ContractState {
-    fn init() -> Self {
-        ContractState {
-            name: Vec::<u8>::new(),
-            symbol: Vec::<u8>::new(),
-            decimals: 0,
-            balances: BTreeMap::default(),
-            allowances: BTreeMap::default(),
-            sum_of_mints: BigInt::default(),
-            sum_of_burns: BigInt::default(),
-        }
-    }
-
-    fn get_balance(&self, addr: &Address) -> i128 {
-        let addr_bytes = address_to_bytes(addr);
-        self.balances.get(&addr_bytes).copied().unwrap_or(0)
-    }
-
-    fn sub_balance(&mut self, addr: &Address, amount: i128) {
-        let addr_bytes = address_to_bytes(addr);
-        let balance = self.get_balance(addr);
-        let new_balance = balance.checked_sub(amount).expect("overflow");
-        assert!(new_balance >= 0);
-        self.balances.insert(addr_bytes, new_balance);
-    }
-
-    fn add_balance(&mut self, addr: &Address, amount: i128) {
-        let addr_bytes = address_to_bytes(addr);
-        let balance = self.get_balance(addr);
-        let new_balance = balance.checked_add(amount).expect("overflow");
-        assert!(new_balance >= 0);
-        self.balances.insert(addr_bytes, new_balance);
-    }
-
-    fn set_allowance(&mut self, from: &Address, spender: &Address, amount: i128) {
-        assert!(amount >= 0);
-        let from_bytes = address_to_bytes(from);
-        let spender_bytes = address_to_bytes(spender);
-        self.allowances.insert((from_bytes, spender_bytes), amount);
-    }
-
-    fn get_allowance(&self, from: &Address, spender: &Address) -> i128 {
-        let from_bytes = address_to_bytes(from);
-        let spender_bytes = address_to_bytes(spender);
-        self.allowances
-            .get(&(from_bytes, spender_bytes))
-            .copied()
-            .unwrap_or(0)
-    }
-
-    fn sub_allowance(&mut self, from: &Address, spender: &Address, amount: i128) {
-        let allowance = self.get_allowance(from, spender);
-        let new_allowance = allowance.checked_sub(amount).expect("overflow");
-        assert!(new_allowance >= 0);
-        self.set_allowance(from, spender, new_allowance);
-    }
 }
 
 /// State that dependso on the `Env` and is reconstructed
@@ -526,6 +820,7 @@ struct CurrentState<'a> {
     accounts: Vec<TestSigner>,
     admin_client: Box<dyn TokenAdminClient<'a> + 'a>,
     token_client: Client<'a>,
+    proxy: Address,
 }
 
 impl<'a> CurrentState<'a> {
@@ -533,33 +828,56 @@ impl<'a> CurrentState<'a> {
         env: &Env,
         config: &Config,
         token_contract_id_bytes: &[u8],
+        proxy_contract_id_bytes: &[u8],
         address_generator: &AddressGenerator,
+        address_types: &[AddressType],
     ) -> Self {
         let token_contract_id =
-            Address::from_string_bytes(&Bytes::from_slice(env, &token_contract_id_bytes));
-        let admin_client = config.new_admin_client(env, &token_contract_id);
-        let token_client = Client::new(env, &token_contract_id);
+            Address::from_string_bytes(&Bytes::from_slice(env, token_contract_id_bytes));
+        let accounts = address_generator.generate_signers(env, address_types);
 
-        let accounts = address_generator.generate_signers(env);
+        let proxy = Address::from_string_bytes(&Bytes::from_slice(env, proxy_contract_id_bytes));
+        env.register_contract(&proxy, ProxyContract);
+
+        let admin = &accounts[0].address;
+        let admin_client = config.new_admin_client(env, &token_contract_id, admin);
+        let token_client = Client::new(env, &token_contract_id);
 
         CurrentState {
             accounts,
             admin_client,
             token_client,
+            proxy,
         }
     }
+
+    /// Call the token through `ProxyContract` on behalf of `caller`.
+    fn forward(
+        &self,
+        caller: &Address,
+        fn_name: &str,
+        args: soroban_sdk::Vec<Val>,
+    ) -> TokenContractResult {
+        let env = &self.token_client.env;
+        let token_contract_id = &self.token_client.address;
+        env.try_invoke_contract::<(), Error>(
+            &self.proxy,
+            &Symbol::new(env, "forward"),
+            ProxyContract::forward_args(env, caller, token_contract_id, fn_name, args),
+        )
+    }
 }
 
-fn assert_state(contract: &ContractState, current: &CurrentState) {
+fn assert_state(model: &dyn TokenModel, metadata: &Metadata, current: &CurrentState) {
     let token_client = &current.token_client;
 
-    assert!(contract.name.eq(&string_to_bytes(token_client.name())));
-    assert!(contract.symbol.eq(&string_to_bytes(token_client.symbol())));
-    assert_eq!(contract.decimals, token_client.decimals());
+    assert!(metadata.name.eq(&string_to_bytes(token_client.name())));
+    assert!(metadata.symbol.eq(&string_to_bytes(token_client.symbol())));
+    assert_eq!(metadata.decimals, token_client.decimals());
 
     for signer in &current.accounts {
         assert_eq!(
-            contract.get_balance(&signer.address),
+            model.balance(&signer.address),
             token_client.balance(&signer.address)
         );
         assert!(token_client.balance(&signer.address) >= 0)
@@ -572,12 +890,12 @@ fn assert_state(contract: &ContractState, current: &CurrentState) {
 
     for (signer1, signer2) in pairs {
         assert_eq!(
-            contract.get_allowance(&signer1.address, &signer2.address),
+            model.allowance(&signer1.address, &signer2.address),
             token_client.allowance(&signer1.address, &signer2.address),
         );
     }
 
-    let sum_of_balances_0 = &contract.sum_of_mints - &contract.sum_of_burns;
+    let sum_of_balances_0 = model.total_supply();
     let sum_of_balances_1 = current
         .accounts
         .iter()
@@ -587,13 +905,27 @@ fn assert_state(contract: &ContractState, current: &CurrentState) {
     assert_eq!(sum_of_balances_0, sum_of_balances_1);
 }
 
+fn assert_invariants(config: &Config, env: &Env, model: &dyn TokenModel, current: &CurrentState) {
+    let token_contract_id = &current.token_client.address;
+
+    for invariant in config.invariants() {
+        if let Err(e) = invariant.check(env, token_contract_id, &current.accounts, model) {
+            panic!("invariant `{}` failed: {e}", invariant.name());
+        }
+    }
+}
+
 /// Advance time, but do it in increments, periodically pinging the contract to
 /// keep it alive.
-fn advance_time(
-    config: &Config,
+///
+/// `reregister_contracts` registers the contracts that aren't in the snapshot
+/// again in each new `Env`, starting with the token.
+pub(crate) fn advance_time(
     mut env: Env,
     token_contract_id_bytes: &[u8],
     ledgers: u32,
+    mut network_id: Option<[u8; 32]>,
+    reregister_contracts: impl Fn(&Env, &Address),
 ) -> Env {
     let to_ledger = env
         .ledger()
@@ -612,18 +944,18 @@ fn advance_time(
 
         let advance_ledgers = next_ledger - curr_ledger;
 
-        env = advance_env(env, advance_ledgers);
+        env = advance_env(env, advance_ledgers, network_id.take());
 
         let token_contract_id =
-            Address::from_string_bytes(&Bytes::from_slice(&env, &token_contract_id_bytes));
-        config.reregister_contract(&env, &token_contract_id);
+            Address::from_string_bytes(&Bytes::from_slice(&env, token_contract_id_bytes));
+        reregister_contracts(&env, &token_contract_id);
 
         if next_ledger == to_ledger {
             break;
         } else {
             // Keep the contract alive
             let token_contract_id =
-                Address::from_string_bytes(&Bytes::from_slice(&env, &token_contract_id_bytes));
+                Address::from_string_bytes(&Bytes::from_slice(&env, token_contract_id_bytes));
             let token_client = Client::new(&env, &token_contract_id);
             let r = token_client.try_allowance(&Address::generate(&env), &Address::generate(&env));
             assert!(r.is_ok());
@@ -633,8 +965,13 @@ fn advance_time(
     env
 }
 
-/// Produces a new `Env` after advancing some number of ledgers
-fn advance_env(prev_env: Env, ledgers: u32) -> Env {
+/// Produces a new `Env` after advancing some number of ledgers,
+/// and optionally moving to another network.
+///
+/// A new network doesn't have the old network's nonces,
+/// so only the network id in their signatures
+/// prevents entries from being replayed there.
+fn advance_env(prev_env: Env, ledgers: u32, network_id: Option<[u8; 32]>) -> Env {
     use soroban_sdk::testutils::Ledger as _;
 
     let secs_per_ledger = {
@@ -663,6 +1000,9 @@ fn advance_env(prev_env: Env, ledgers: u32) -> Env {
                 .timestamp
                 .checked_add(ledger_time)
                 .expect("end of time");
+            if let Some(network_id) = network_id {
+                ledger.network_id = network_id;
+            }
         });
 
         env
@@ -680,11 +1020,14 @@ fn advance_env(prev_env: Env, ledgers: u32) -> Env {
             .expect("end of time");
 
         purge_expired_entries(&mut snapshot);
-        // todo purge events and auths?
 
-        let env = Env::from_snapshot(snapshot);
+        if let Some(network_id) = network_id {
+            snapshot.ledger.network_id = network_id;
+            purge_nonces(&mut snapshot);
+        }
+        // todo purge events and auths?
 
-        env
+        Env::from_snapshot(snapshot)
     }
 }
 
@@ -693,11 +1036,7 @@ fn purge_expired_entries(snapshot: &mut Snapshot) {
         let (_key, (_entry, expiration_ledger)) = entry;
 
         if let Some(expiration_ledger) = expiration_ledger {
-            if *expiration_ledger < snapshot.ledger.sequence_number {
-                false
-            } else {
-                true
-            }
+            *expiration_ledger >= snapshot.ledger.sequence_number
         } else {
             // what does it mean for storage to not have an expiration ledger?
             true
@@ -705,17 +1044,159 @@ fn purge_expired_entries(snapshot: &mut Snapshot) {
     });
 }
 
-fn verify_token_contract_result(env: &Env, r: &TokenContractResult) {
-    match r {
-        Err(Ok(e)) => {
-            if e.is_type(ScErrorType::WasmVm) && e.is_code(ScErrorCode::InvalidAction) {
-                let msg = "contract failed with InvalidAction - unexpected panic?";
-                eprintln!("{msg}");
-                print_diagnostics(env);
-                panic!("{msg}");
-            }
+fn purge_nonces(snapshot: &mut Snapshot) {
+    snapshot.ledger.ledger_entries.retain(|entry| {
+        let (key, _) = entry;
+
+        !matches!(
+            key.as_ref(),
+            LedgerKey::ContractData(LedgerKeyContractData {
+                key: ScVal::LedgerKeyNonce(_),
+                ..
+            })
+        )
+    });
+}
+
+pub(crate) fn verify_token_contract_result(env: &Env, r: &TokenContractResult) {
+    if let Err(Ok(e)) = r {
+        if e.is_type(ScErrorType::WasmVm) && e.is_code(ScErrorCode::InvalidAction) {
+            let msg = "contract failed with InvalidAction - unexpected panic?";
+            eprintln!("{msg}");
+            print_diagnostics(env);
+            panic!("{msg}");
         }
-        _ => {}
+    }
+}
+
+/// Assert that a successful call required authorization
+/// from exactly `signer`, for exactly this call and its arguments.
+///
+/// Calls made through `proxy` are authorized as a call to the proxy,
+/// with the token call as its only sub-invocation.
+pub(crate) fn assert_auths(
+    env: &Env,
+    signer: &Address,
+    token_contract_id: &Address,
+    fn_name: &str,
+    args: soroban_sdk::Vec<Val>,
+    proxy: Option<&Address>,
+) {
+    let token_invocation = AuthorizedInvocation {
+        function: AuthorizedFunction::Contract((
+            token_contract_id.clone(),
+            Symbol::new(env, fn_name),
+            args.clone(),
+        )),
+        sub_invocations: vec![],
+    };
+
+    let invocation = match proxy {
+        Some(proxy) => AuthorizedInvocation {
+            function: AuthorizedFunction::Contract((
+                proxy.clone(),
+                Symbol::new(env, "forward"),
+                ProxyContract::forward_args(env, signer, token_contract_id, fn_name, args),
+            )),
+            sub_invocations: vec![token_invocation],
+        },
+        None => token_invocation,
+    };
+
+    let expected = vec![(signer.clone(), invocation)];
+
+    let actual = env.auths();
+    if actual != expected {
+        let msg = format!(
+            "unexpected auths for `{fn_name}`\nexpected: {expected:#?}\nactual: {actual:#?}"
+        );
+        eprintln!("{msg}");
+        print_diagnostics(env);
+        panic!("{msg}");
+    }
+}
+
+/// The reasons the model predicts a call will fail.
+#[derive(Clone, Copy, Default)]
+struct ExpectedFailure {
+    negative_amount: bool,
+    unauthorized: bool,
+    /// Calls through the proxy fail authorization
+    /// before the token checks anything.
+    via_proxy: bool,
+    insufficient_allowance: bool,
+    insufficient_balance: bool,
+}
+
+/// The errors the model allows a failing call to return.
+#[derive(Debug)]
+pub(crate) enum ExpectedError {
+    /// Any error, since the call may fail for a reason
+    /// the token didn't declare errors for.
+    Any,
+    /// One of the errors declared for the reasons the call should fail.
+    OneOf(RustVec<Error>),
+}
+
+/// The errors the token may fail with,
+/// for any of the reasons it should fail.
+///
+/// `None` if the model predicts no failure.
+fn expected_error(error_codes: &ErrorCodes, failure: &ExpectedFailure) -> Option<ExpectedError> {
+    let reasons: RustVec<&RustVec<Error>> = if failure.via_proxy && failure.unauthorized {
+        vec![&error_codes.unauthorized]
+    } else {
+        [
+            (failure.negative_amount, &error_codes.negative_amount),
+            (failure.unauthorized, &error_codes.unauthorized),
+            (
+                failure.insufficient_allowance,
+                &error_codes.insufficient_allowance,
+            ),
+            (
+                failure.insufficient_balance,
+                &error_codes.insufficient_balance,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(fails, errors)| fails.then_some(errors))
+        .collect()
+    };
+
+    if reasons.is_empty() {
+        None
+    } else if reasons.iter().any(|errors| errors.is_empty()) {
+        Some(ExpectedError::Any)
+    } else {
+        Some(ExpectedError::OneOf(
+            reasons.into_iter().flatten().copied().collect(),
+        ))
+    }
+}
+
+/// Assert that a call failed if the model predicts it should,
+/// and that it failed with an error the token declared
+/// for one of the reasons it should fail.
+fn assert_expected_error(
+    env: &Env,
+    error_codes: &ErrorCodes,
+    failure: &ExpectedFailure,
+    r: &TokenContractResult,
+) {
+    let Some(expected) = expected_error(error_codes, failure) else {
+        return;
+    };
+
+    let ok = match &expected {
+        ExpectedError::Any => r.is_err(),
+        ExpectedError::OneOf(errors) => matches!(r, Err(Ok(e)) if errors.contains(e)),
+    };
+
+    if !ok {
+        let msg = format!("expected error {expected:?}, got {r:?}");
+        eprintln!("{msg}");
+        print_diagnostics(env);
+        panic!("{msg}");
     }
 }
 
@@ -726,115 +1207,594 @@ fn print_diagnostics(env: &Env) {
     }
 }
 
-#[contract]
-pub struct MockAuthContract;
+/// A contract that calls the token on behalf of a user,
+/// like a vault or exchange would,
+/// so that the user's authorization has the token call
+/// as a sub-invocation.
+///
+/// This implements `ContractFunctionSet` directly instead of using `#[contractimpl]`
+/// so that failed auths and token calls unwind into the host
+/// and fail the call with the same error a WASM contract would.
+pub struct ProxyContract;
+
+impl ProxyContract {
+    /// The arguments to `forward(caller, token, fn_name, args)`.
+    pub(crate) fn forward_args(
+        env: &Env,
+        caller: &Address,
+        token_contract_id: &Address,
+        fn_name: &str,
+        args: soroban_sdk::Vec<Val>,
+    ) -> soroban_sdk::Vec<Val> {
+        (caller, token_contract_id, Symbol::new(env, fn_name), args).into_val(env)
+    }
+}
+
+impl ContractFunctionSet for ProxyContract {
+    fn call(&self, func: &str, env: Env, args: &[Val]) -> Option<Val> {
+        if func != "forward" {
+            return None;
+        }
 
-#[contractimpl]
-impl MockAuthContract {
-    #[allow(non_snake_case)]
-    pub fn __check_auth(_signature_payload: Val, _signatures: Val, _auth_context: Val) {}
+        let [caller, token_contract_id, fn_name, args] = args else {
+            panic!("wrong number of arguments to forward");
+        };
+        let caller = Address::try_from_val(&env, caller).unwrap();
+        let token_contract_id = Address::try_from_val(&env, token_contract_id).unwrap();
+        let fn_name = Symbol::try_from_val(&env, fn_name).unwrap();
+        let args = soroban_sdk::Vec::<Val>::try_from_val(&env, args).unwrap();
+
+        caller.require_auth();
+
+        Some(env.invoke_contract::<Val>(&token_contract_id, &fn_name, args))
+    }
+}
+
+/// A call to the token made by a command.
+struct TokenCall<'a> {
+    fn_name: &'a str,
+    args: soroban_sdk::Vec<Val>,
+    /// The account the token should require authorization from.
+    signer: usize,
+    auth: &'a CallAuth,
+    /// The recipient and amount of a mint,
+    /// which is made through the admin client
+    /// unless it goes through the proxy.
+    mint: Option<(usize, i128)>,
+}
+
+/// The authorization a command needs.
+struct CommandAuths<'a> {
+    fn_name: &'a str,
+    args: soroban_sdk::Vec<Val>,
+    /// The account the token should require authorization from.
+    required_signer: usize,
+    /// Which accounts sign, how the required signer's entry is corrupted,
+    /// when the entries expire, and whether the required signer
+    /// calls the token through `ProxyContract`.
+    auth: &'a CallAuth,
+}
+
+/// The authorization entries for a command.
+struct MockAuths {
+    entries: RustVec<SorobanAuthorizationEntry>,
+    /// Whether the required signer's entry authorizes the call:
+    /// the signer signed, the entry wasn't corrupted,
+    /// and the signer's account contract accepts the call.
+    authorized: bool,
+    /// The required signer's entry,
+    /// which is consumed if the call succeeds.
+    required_entry: Option<AcceptedAuth>,
+}
+
+/// An entry that authorized a successful call.
+struct AcceptedAuth {
+    credentials: SorobanAddressCredentials,
+    call: AuthorizedCall,
+    /// The last ledger the host keeps the entry's nonce,
+    /// after which the nonce can be reused.
+    nonce_live_until: u32,
+    /// The network the nonce was used on.
+    network_id: [u8; 32],
+}
+
+impl AcceptedAuth {
+    fn entry(&self) -> SorobanAuthorizationEntry {
+        SorobanAuthorizationEntry {
+            credentials: SorobanCredentials::Address(self.credentials.clone()),
+            root_invocation: self.call.invocation(),
+        }
+    }
 }
 
+/// The call an authorization entry is for.
+#[derive(Clone)]
+struct AuthorizedCall {
+    /// The call to the token.
+    token_function: InvokeContractArgs,
+    /// The call to `ProxyContract::forward` that makes the token call,
+    /// if it goes through the proxy.
+    proxy_function: Option<InvokeContractArgs>,
+}
+
+impl AuthorizedCall {
+    /// The function at the root of the entry's invocation tree.
+    fn root_function(&self) -> &InvokeContractArgs {
+        self.proxy_function.as_ref().unwrap_or(&self.token_function)
+    }
+
+    fn root_function_mut(&mut self) -> &mut InvokeContractArgs {
+        self.proxy_function
+            .as_mut()
+            .unwrap_or(&mut self.token_function)
+    }
+
+    fn invocation(&self) -> SorobanAuthorizedInvocation {
+        let token_invocation = SorobanAuthorizedInvocation {
+            function: SorobanAuthorizedFunction::ContractFn(self.token_function.clone()),
+            sub_invocations: Default::default(),
+        };
+        match &self.proxy_function {
+            Some(proxy_function) => SorobanAuthorizedInvocation {
+                function: SorobanAuthorizedFunction::ContractFn(proxy_function.clone()),
+                sub_invocations: vec![token_invocation].try_into().unwrap(),
+            },
+            None => token_invocation,
+        }
+    }
+}
+
+/// Build signed authorization entries for the signers
+/// whose flags in `auths` are set.
 fn mock_auths_for_command(
     env: &Env,
-    fn_name: &str,
-    auths: &[bool],
+    command_auths: &CommandAuths,
     current_state: &CurrentState,
     token_contract_id_bytes: &[u8],
     signature_nonce: &mut i64,
-    args: soroban_sdk::Vec<Val>,
-) {
-    let curr_ledger = env.ledger().sequence();
-    let max_entry_ttl = env.ledger().get().max_entry_ttl;
-    let expiration_ledger = curr_ledger + max_entry_ttl - 1;
+    accepted_auths: &[AcceptedAuth],
+) -> MockAuths {
+    let ledger_info = env.ledger().get();
+    let curr_ledger = ledger_info.sequence_number;
+    let max_expiration_ledger = curr_ledger + ledger_info.max_entry_ttl - 1;
+    let expiration_ledger = match &command_auths.auth.signature_expiration {
+        None => max_expiration_ledger,
+        Some(SignatureExpiration::Expired(n)) => curr_ledger.saturating_sub(*n as u32 + 1),
+        Some(SignatureExpiration::CurrentLedger) => curr_ledger,
+        Some(SignatureExpiration::After(n)) => curr_ledger.saturating_add(*n),
+        Some(SignatureExpiration::InsideMaxTtl(n)) => {
+            max_expiration_ledger.saturating_sub(*n as u32)
+        }
+        Some(SignatureExpiration::BeyondMaxTtl(n)) => {
+            max_expiration_ledger.saturating_add(*n as u32 + 1)
+        }
+    };
 
     let token_contract_id =
         Address::from_string_bytes(&Bytes::from_slice(env, token_contract_id_bytes));
     let token_contract_sc_address = ScAddress::try_from(token_contract_id).unwrap();
 
     let mut auth_entries = RustVec::new();
+    let mut authorized = false;
+    let mut required_entry = None;
 
-    for i in 0..NUMBER_OF_ADDRESSES {
-        if auths[i] {
-            let signer = &current_state.accounts[i];
-            let sc_address = ScAddress::try_from(signer.address.clone()).unwrap();
+    for (signer_index, signer) in current_state
+        .accounts
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !command_auths.auth.declined.contains(index))
+    {
+        let sc_address = ScAddress::try_from(signer.address.clone()).unwrap();
 
-            let is_contract_address = signer.key.is_none();
+        if let Some(account_contract) = &signer.account_contract {
+            account_contract.register(env, &signer.address);
+        }
 
-            // contract addresses need to have registered contracts to be authorizers,
-            // at least according to the sdk's mock_auths method
-            if is_contract_address {
-                env.register_contract(&signer.address, MockAuthContract);
+        let mut credentials = SorobanAddressCredentials {
+            address: sc_address,
+            nonce: *signature_nonce,
+            signature_expiration_ledger: expiration_ledger,
+            signature: ScVal::Void, // updated below
+        };
+
+        let token_function = InvokeContractArgs {
+            contract_address: token_contract_sc_address.clone(),
+            function_name: ScSymbol(command_auths.fn_name.try_into().unwrap()),
+            args: VecM::try_from(command_auths.args.clone()).unwrap(),
+        };
+
+        let proxy_function = command_auths.auth.via_proxy.then(|| {
+            let caller = &current_state.accounts[command_auths.required_signer].address;
+            InvokeContractArgs {
+                contract_address: ScAddress::try_from(current_state.proxy.clone()).unwrap(),
+                function_name: ScSymbol("forward".try_into().unwrap()),
+                args: VecM::try_from(ProxyContract::forward_args(
+                    env,
+                    caller,
+                    &current_state.token_client.address,
+                    command_auths.fn_name,
+                    command_auths.args.clone(),
+                ))
+                .unwrap(),
             }
+        });
 
-            let mut credentials = SorobanAddressCredentials {
-                address: sc_address,
-                nonce: *signature_nonce,
-                signature_expiration_ledger: expiration_ledger,
-                signature: ScVal::Void, // updated below for non-contract addresses
-            };
+        let mut call = AuthorizedCall {
+            token_function,
+            proxy_function,
+        };
 
-            let root_invocation = SorobanAuthorizedInvocation {
-                function: SorobanAuthorizedFunction::ContractFn(InvokeContractArgs {
-                    contract_address: token_contract_sc_address.clone(),
-                    function_name: ScSymbol(fn_name.try_into().unwrap()),
-                    args: VecM::try_from(args.clone()).unwrap(),
-                }),
-                sub_invocations: Default::default(),
-            };
+        let mutation = command_auths
+            .auth
+            .auth_mutation
+            .as_ref()
+            .filter(|_| signer_index == command_auths.required_signer);
+
+        if signer_index == command_auths.required_signer {
+            authorized = !signer.rejects(&call.invocation());
+        }
 
-            if let Some(key) = &signer.key {
-                let signature_payload_preimage =
-                    HashIdPreimage::SorobanAuthorization(HashIdPreimageSorobanAuthorization {
-                        network_id: env
-                            .host()
-                            .with_ledger_info(|li: &LedgerInfo| Ok(li.network_id))
-                            .unwrap()
-                            .try_into()
-                            .unwrap(),
-                        invocation: root_invocation.clone(),
-                        nonce: *signature_nonce,
-                        signature_expiration_ledger: expiration_ledger,
-                    });
-
-                let mut buf = vec![];
-                let mut unlimited_buf = Limited::new(&mut buf, Limits::none());
-                signature_payload_preimage
-                    .write_xdr(&mut unlimited_buf)
-                    .unwrap();
-                let signature_payload: [u8; 32] = Sha256::digest(&buf).try_into().unwrap();
-
-                let signature = sign_payload_for_account(env, key, &signature_payload);
-                let signatures = soroban_sdk::vec![env, signature];
-                credentials.signature = signatures.try_into().unwrap();
+        if let Some(mutation) = mutation {
+            if corrupt_auth_entry(
+                env,
+                mutation,
+                &mut credentials,
+                call.root_function_mut(),
+                accepted_auths,
+            ) {
+                authorized = false;
             }
+        }
 
-            *signature_nonce += 1;
+        let root_invocation = call.invocation();
 
-            let auth_entry = SorobanAuthorizationEntry {
-                credentials: SorobanCredentials::Address(credentials),
-                root_invocation,
-            };
-            auth_entries.push(auth_entry);
+        // The host rejects signatures that have expired,
+        // or that would outlive the longest allowed TTL.
+        if !(curr_ledger..=max_expiration_ledger).contains(&credentials.signature_expiration_ledger)
+        {
+            authorized = false;
         }
+
+        sign_auth_entry(env, signer, &mut credentials, &root_invocation);
+
+        if let Some(mutation) = mutation {
+            if corrupt_signature(
+                env,
+                mutation,
+                &current_state.accounts,
+                signer,
+                &mut credentials,
+                &root_invocation,
+            ) {
+                authorized = false;
+            }
+        }
+
+        *signature_nonce += 1;
+
+        let auth_entry = SorobanAuthorizationEntry {
+            credentials: SorobanCredentials::Address(credentials.clone()),
+            root_invocation: root_invocation.clone(),
+        };
+
+        if signer_index == command_auths.required_signer {
+            // Nonces are stored as temporary entries,
+            // which live at least the minimum temporary TTL.
+            let min_live_until = curr_ledger + ledger_info.min_temp_entry_ttl - 1;
+            required_entry = Some(AcceptedAuth {
+                credentials: credentials.clone(),
+                call: call.clone(),
+                nonce_live_until: credentials.signature_expiration_ledger.max(min_live_until),
+                network_id: network_id(env),
+            });
+        }
+
+        match mutation {
+            Some(AuthMutation::DuplicateEntry) => {
+                auth_entries.push(auth_entry.clone());
+            }
+            Some(AuthMutation::ExtraEntry) => {
+                // The same call, but on another contract.
+                credentials.nonce = *signature_nonce;
+                corrupt_contract_address(env, call.root_function_mut());
+                let root_invocation = call.invocation();
+                sign_auth_entry(env, signer, &mut credentials, &root_invocation);
+
+                *signature_nonce += 1;
+
+                auth_entries.push(SorobanAuthorizationEntry {
+                    credentials: SorobanCredentials::Address(credentials),
+                    root_invocation,
+                });
+            }
+            _ => {}
+        }
+
+        auth_entries.push(auth_entry);
     }
 
-    env.set_auths(&auth_entries);
+    MockAuths {
+        entries: auth_entries,
+        authorized,
+        required_entry,
+    }
+}
+
+/// Corrupt an authorization entry before it is signed.
+///
+/// Returns whether the entry no longer authorizes the call,
+/// either because it was changed or because its nonce was already used.
+fn corrupt_auth_entry(
+    env: &Env,
+    mutation: &AuthMutation,
+    credentials: &mut SorobanAddressCredentials,
+    function: &mut InvokeContractArgs,
+    accepted_auths: &[AcceptedAuth],
+) -> bool {
+    let original = (function.clone(), credentials.signature_expiration_ledger);
+
+    match mutation {
+        AuthMutation::FunctionName(i) => corrupt_function_name(function, *i),
+        AuthMutation::SwapArgs(i, j) => swap_args(function, *i, *j),
+        AuthMutation::AlterAmount(amount) => alter_amount(function, *amount),
+        AuthMutation::TruncateArgs => truncate_args(function),
+        AuthMutation::ContractAddress => corrupt_contract_address(env, function),
+        AuthMutation::ExpiredSignature => expire_signature(env, credentials),
+        AuthMutation::ReuseNonce(i) => reuse_nonce(credentials, accepted_auths, *i),
+        AuthMutation::DuplicateEntry
+        | AuthMutation::ExtraEntry
+        | AuthMutation::WrongKey(_)
+        | AuthMutation::FlipSignatureBit(_)
+        | AuthMutation::TruncateSignatures
+        | AuthMutation::WrongPublicKey(..)
+        | AuthMutation::DuplicateSignature(_)
+        | AuthMutation::OtherNetwork(_) => {}
+    }
+
+    let changed = (function.clone(), credentials.signature_expiration_ledger) != original;
+    changed || nonce_used(env, credentials, accepted_auths)
+}
+
+/// Authorize a different function of the token.
+fn corrupt_function_name(function: &mut InvokeContractArgs, i: u8) {
+    let fn_name = TOKEN_FN_NAMES[i as usize % TOKEN_FN_NAMES.len()];
+    function.function_name = ScSymbol(fn_name.try_into().unwrap());
+}
+
+fn swap_args(function: &mut InvokeContractArgs, i: u8, j: u8) {
+    let mut args = function.args.to_vec();
+    if !args.is_empty() {
+        let len = args.len();
+        args.swap(i as usize % len, j as usize % len);
+    }
+    function.args = args.try_into().unwrap();
+}
+
+/// Replace the `i128` arguments with `amount`.
+fn alter_amount(function: &mut InvokeContractArgs, amount: i128) {
+    let mut args = function.args.to_vec();
+    for arg in &mut args {
+        if let ScVal::I128(_) = arg {
+            *arg = ScVal::I128(Int128Parts {
+                hi: (amount >> 64) as i64,
+                lo: amount as u64,
+            });
+        }
+    }
+    function.args = args.try_into().unwrap();
+}
+
+fn truncate_args(function: &mut InvokeContractArgs) {
+    let mut args = function.args.to_vec();
+    args.pop();
+    function.args = args.try_into().unwrap();
+}
+
+/// Authorize the call on a contract that doesn't exist.
+fn corrupt_contract_address(env: &Env, function: &mut InvokeContractArgs) {
+    function.contract_address = ScAddress::try_from(Address::generate(env)).unwrap();
+}
+
+/// Expire the entry at the previous ledger.
+fn expire_signature(env: &Env, credentials: &mut SorobanAddressCredentials) {
+    if let Some(ledger) = env.ledger().sequence().checked_sub(1) {
+        credentials.signature_expiration_ledger = ledger;
+    }
+}
+
+/// Use the nonce of one of the accepted entries.
+fn reuse_nonce(
+    credentials: &mut SorobanAddressCredentials,
+    accepted_auths: &[AcceptedAuth],
+    i: u16,
+) {
+    if !accepted_auths.is_empty() {
+        let accepted = &accepted_auths[i as usize % accepted_auths.len()];
+        credentials.nonce = accepted.credentials.nonce;
+    }
 }
 
-#[contracttype]
-pub(crate) struct AccountEd25519Signature {
-    pub(crate) public_key: BytesN<32>,
-    pub(crate) signature: BytesN<64>,
+/// Whether the host still has the entry's nonce.
+///
+/// Nonces are per address, so this only matters
+/// if this signer used the nonce.
+fn nonce_used(
+    env: &Env,
+    credentials: &SorobanAddressCredentials,
+    accepted_auths: &[AcceptedAuth],
+) -> bool {
+    let curr_ledger = env.ledger().sequence();
+    let curr_network_id = network_id(env);
+    accepted_auths.iter().any(|accepted| {
+        accepted.nonce_live_until >= curr_ledger
+            && accepted.network_id == curr_network_id
+            && accepted.credentials.address == credentials.address
+            && accepted.credentials.nonce == credentials.nonce
+    })
 }
 
-fn sign_payload_for_account(
+/// Corrupt the signature of an authorization entry after it is signed.
+///
+/// Returns whether the signature no longer authorizes the call.
+fn corrupt_signature(
     env: &Env,
-    signer: &SigningKey,
-    payload: &[u8],
-) -> AccountEd25519Signature {
-    AccountEd25519Signature {
-        public_key: BytesN::<32>::try_from_val(env, &signer.verifying_key().to_bytes()).unwrap(),
-        signature: BytesN::<64>::try_from_val(env, &signer.sign(payload).to_bytes()).unwrap(),
+    mutation: &AuthMutation,
+    accounts: &[TestSigner],
+    signer: &TestSigner,
+    credentials: &mut SorobanAddressCredentials,
+    root_invocation: &SorobanAuthorizedInvocation,
+) -> bool {
+    let other_keys: RustVec<&SigningKey> = accounts
+        .iter()
+        .filter(|account| account.address != signer.address)
+        .filter_map(|account| account.key.as_ref())
+        .collect();
+    let other_key =
+        |i: u8| (!other_keys.is_empty()).then(|| other_keys[i as usize % other_keys.len()]);
+
+    if let AuthMutation::OtherNetwork(i) = mutation {
+        let passphrase = NETWORK_PASSPHRASES[*i as usize % NETWORK_PASSPHRASES.len()];
+        let other_network_id = passphrase_network_id(passphrase);
+        if other_network_id == network_id(env) {
+            return false;
+        }
+
+        let payload = signature_payload(other_network_id, credentials, root_invocation);
+        credentials.signature = signer.sign(env, &payload);
+        // Only signatures over the payload are bound to the network.
+        return credentials.signature != ScVal::Void;
     }
+
+    let payload = signature_payload(network_id(env), credentials, root_invocation);
+
+    // Ed25519 account contracts take a single signature.
+    if let ScVal::Bytes(signature) = &credentials.signature {
+        let mut bytes = signature.to_vec();
+        match mutation {
+            AuthMutation::WrongKey(i) => {
+                let Some(key) = other_key(*i) else {
+                    return false;
+                };
+                bytes = key.sign(&payload).to_bytes().to_vec();
+            }
+            AuthMutation::FlipSignatureBit(bit) => {
+                let bit = *bit as usize % (bytes.len() * 8);
+                bytes[bit / 8] ^= 1 << (bit % 8);
+            }
+            AuthMutation::TruncateSignatures => {
+                bytes.pop();
+            }
+            _ => return false,
+        }
+        credentials.signature = ScVal::Bytes(bytes.try_into().unwrap());
+        return true;
+    }
+
+    // Other signers take a vector of signatures, or none at all.
+    let Ok(signatures) = soroban_sdk::Vec::<AccountEd25519Signature>::try_from_val(
+        env,
+        &Val::try_from_val(env, &credentials.signature).unwrap(),
+    ) else {
+        return false;
+    };
+    let mut signatures: RustVec<_> = signatures.iter().collect();
+    if signatures.is_empty() {
+        return false;
+    }
+    let len = signatures.len();
+
+    let rejected = match mutation {
+        AuthMutation::WrongKey(i) => {
+            let Some(key) = other_key(*i) else {
+                return false;
+            };
+            signatures = vec![sign_payload_for_account(env, key, &payload)];
+            true
+        }
+        AuthMutation::FlipSignatureBit(bit) => {
+            let signature = &mut signatures[*bit as usize / 512 % len];
+            let mut bytes = signature.signature.to_array();
+            bytes[*bit as usize % 512 / 8] ^= 1 << (bit % 8);
+            signature.signature = BytesN::from_array(env, &bytes);
+            true
+        }
+        AuthMutation::TruncateSignatures => {
+            signatures.pop();
+            let public_keys: RustVec<[u8; 32]> = signatures
+                .iter()
+                .map(|signature| signature.public_key.to_array())
+                .collect();
+            signer.rejects_signers(&public_keys)
+        }
+        AuthMutation::WrongPublicKey(i, j) => {
+            let Some(key) = other_key(*j) else {
+                return false;
+            };
+            signatures[*i as usize % len].public_key =
+                BytesN::from_array(env, &key.verifying_key().to_bytes());
+            true
+        }
+        AuthMutation::DuplicateSignature(i) => {
+            let i = *i as usize % len;
+            signatures.insert(i, signatures[i].clone());
+            true
+        }
+        _ => return false,
+    };
+
+    let signatures = soroban_sdk::Vec::from_slice(env, &signatures);
+    credentials.signature = signatures.try_into().unwrap();
+    rejected
+}
+
+/// Networks other than the fuzzer's to mistakenly sign for.
+const NETWORK_PASSPHRASES: &[&str] = &[
+    "Public Global Stellar Network ; September 2015",
+    "Test SDF Network ; September 2015",
+    "Test SDF Future Network ; October 2022",
+];
+
+/// The id of the network with this passphrase.
+pub(crate) fn passphrase_network_id(network_passphrase: &str) -> [u8; 32] {
+    Sha256::digest(network_passphrase).into()
+}
+
+fn network_id(env: &Env) -> [u8; 32] {
+    env.host()
+        .with_ledger_info(|li: &LedgerInfo| Ok(li.network_id))
+        .unwrap()
+}
+
+/// The payload the signer signs for an authorization entry
+/// on the network `network_id`.
+fn signature_payload(
+    network_id: [u8; 32],
+    credentials: &SorobanAddressCredentials,
+    root_invocation: &SorobanAuthorizedInvocation,
+) -> [u8; 32] {
+    let signature_payload_preimage =
+        HashIdPreimage::SorobanAuthorization(HashIdPreimageSorobanAuthorization {
+            network_id: network_id.into(),
+            invocation: root_invocation.clone(),
+            nonce: credentials.nonce,
+            signature_expiration_ledger: credentials.signature_expiration_ledger,
+        });
+
+    let mut buf = vec![];
+    let mut unlimited_buf = Limited::new(&mut buf, Limits::none());
+    signature_payload_preimage
+        .write_xdr(&mut unlimited_buf)
+        .unwrap();
+    Sha256::digest(&buf).into()
+}
+
+/// Sign the entry with the signer's keys, or for its account contract.
+fn sign_auth_entry(
+    env: &Env,
+    signer: &TestSigner,
+    credentials: &mut SorobanAddressCredentials,
+    root_invocation: &SorobanAuthorizedInvocation,
+) {
+    let signature_payload = signature_payload(network_id(env), credentials, root_invocation);
+    credentials.signature = signer.sign(env, &signature_payload);
 }
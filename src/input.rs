@@ -1,28 +1,56 @@
-use crate::addrgen::AddressGenerator;
+use crate::addrgen::{AddressGenerator, AddressType};
 use crate::DAY_IN_LEDGERS;
 use arbitrary::Unstructured;
+use serde::{Deserialize, Serialize};
 use soroban_sdk::testutils::arbitrary::arbitrary;
 use std::vec::Vec as RustVec;
 
-pub const NUMBER_OF_ADDRESSES: usize = 3;
+/// The number of addresses the fuzzer starts with,
+/// unless configured with [`Config::with_number_of_addresses`].
+///
+/// [`Config::with_number_of_addresses`]: crate::Config::with_number_of_addresses
+pub const DEFAULT_NUMBER_OF_ADDRESSES: usize = 3;
+
+/// The most addresses a run can have,
+/// including those introduced by [`Command::FreshAddress`].
+pub const MAX_NUMBER_OF_ADDRESSES: usize = 16;
+
+/// The fuzzer draws account indexes below this.
+///
+/// Account indexes in commands are taken modulo the number of addresses
+/// when the command is executed, and this is a multiple of every
+/// number of addresses, so each address is as likely as the others.
+pub const ACCOUNT_INDEX_RANGE: usize = 720720;
+
+const _: () = {
+    let mut number_of_addresses = 1;
+    while number_of_addresses <= MAX_NUMBER_OF_ADDRESSES {
+        assert!(ACCOUNT_INDEX_RANGE.is_multiple_of(number_of_addresses));
+        number_of_addresses += 1;
+    }
+};
 
 /// Input generated by the fuzzer as the argument to `fuzz_target!`.
 ///
 /// It consists of addresses and a series of commands that operate on them.
-#[derive(Clone, Debug, arbitrary::Arbitrary)]
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
 pub struct Input {
     pub address_generator: AddressGenerator,
     pub transactions: RustVec<Transaction>,
 }
 
-#[derive(Clone, Debug, arbitrary::Arbitrary)]
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
 pub struct Transaction {
     pub commands: RustVec<Command>,
     #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(1..=DAY_IN_LEDGERS))]
     pub advance_ledgers: u32,
+    /// Move the ledger to a new network after the transaction,
+    /// as if the token were deployed there with the same state.
+    #[arbitrary(with = |u: &mut Unstructured| u.ratio(1, 20))]
+    pub switch_network: bool,
 }
 
-#[derive(Clone, Debug, arbitrary::Arbitrary)]
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
 pub enum Command {
     Mint(MintInput),
     Approve(ApproveInput),
@@ -32,207 +60,412 @@ pub enum Command {
     Burn(BurnInput),
     ApproveAndTransferFrom(ApproveAndTransferFromInput),
     ApproveAndBurnFrom(ApproveAndBurnFromInput),
+    Extension(ExtensionInput),
+    ReplayAuth(ReplayAuthInput),
+    FreshAddress(FreshAddressInput),
+}
+
+impl Command {
+    /// The number of variants, which the derived `Arbitrary`
+    /// scales its choice of variant to,
+    /// and the artifact encoder scales back from.
+    pub(crate) const VARIANTS: u64 = 11;
 }
 
-#[derive(Clone, Debug, arbitrary::Arbitrary)]
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
 pub struct MintInput {
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(i128::MIN..=i128::MAX))]
-    pub amount: i128,
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=NUMBER_OF_ADDRESSES - 1))]
+    pub amount: Amount,
+    #[arbitrary(with = arbitrary_account_index)]
     pub to_account_index: usize,
-    #[arbitrary(with = |u: &mut Unstructured| {
-        // biased bool - only sometimes decline the auth
-        Ok(<[bool; NUMBER_OF_ADDRESSES]>::try_from(
-            std::iter::from_fn(|| Some(u.ratio(9, 10).unwrap_or(true)))
-                .take(NUMBER_OF_ADDRESSES)
-                .collect::<Vec<_>>()
-        ).unwrap())
-    })]
-    pub auths: [bool; NUMBER_OF_ADDRESSES],
-}
-
-#[derive(Clone, Debug, arbitrary::Arbitrary)]
+    pub auth: CallAuth,
+}
+
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
 pub struct ApproveInput {
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(i128::MIN..=i128::MAX))]
-    pub amount: i128,
+    pub amount: Amount,
     #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=DAY_IN_LEDGERS * 30))]
     pub expiration_ledger: u32,
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=NUMBER_OF_ADDRESSES - 1))]
+    #[arbitrary(with = arbitrary_account_index)]
     pub from_account_index: usize,
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=NUMBER_OF_ADDRESSES - 1))]
+    #[arbitrary(with = arbitrary_account_index)]
     pub spender_account_index: usize,
-    #[arbitrary(with = |u: &mut Unstructured| {
-        // biased bool - only sometimes decline the auth
-        Ok(<[bool; NUMBER_OF_ADDRESSES]>::try_from(
-            std::iter::from_fn(|| Some(u.ratio(9, 10).unwrap_or(true)))
-                .take(NUMBER_OF_ADDRESSES)
-                .collect::<Vec<_>>()
-        ).unwrap())
-    })]
-    pub auths: [bool; NUMBER_OF_ADDRESSES],
-}
-
-#[derive(Clone, Debug, arbitrary::Arbitrary)]
+    pub auth: CallAuth,
+}
+
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
 pub struct TransferFromInput {
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(i128::MIN..=i128::MAX))]
-    pub amount: i128,
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=NUMBER_OF_ADDRESSES - 1))]
+    pub amount: Amount,
+    #[arbitrary(with = arbitrary_account_index)]
     pub spender_account_index: usize,
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=NUMBER_OF_ADDRESSES - 1))]
+    #[arbitrary(with = arbitrary_account_index)]
     pub from_account_index: usize,
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=NUMBER_OF_ADDRESSES - 1))]
+    #[arbitrary(with = arbitrary_account_index)]
     pub to_account_index: usize,
-    #[arbitrary(with = |u: &mut Unstructured| {
-        // biased bool - only sometimes decline the auth
-        Ok(<[bool; NUMBER_OF_ADDRESSES]>::try_from(
-            std::iter::from_fn(|| Some(u.ratio(9, 10).unwrap_or(true)))
-                .take(NUMBER_OF_ADDRESSES)
-                .collect::<Vec<_>>()
-        ).unwrap())
-    })]
-    pub auths: [bool; NUMBER_OF_ADDRESSES],
-}
-
-#[derive(Clone, Debug, arbitrary::Arbitrary)]
+    pub auth: CallAuth,
+}
+
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
 pub struct TransferInput {
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(i128::MIN..=i128::MAX))]
-    pub amount: i128,
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=NUMBER_OF_ADDRESSES - 1))]
+    pub amount: Amount,
+    #[arbitrary(with = arbitrary_account_index)]
     pub from_account_index: usize,
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=NUMBER_OF_ADDRESSES - 1))]
+    #[arbitrary(with = arbitrary_account_index)]
     pub to_account_index: usize,
-    #[arbitrary(with = |u: &mut Unstructured| {
-        // biased bool - only sometimes decline the auth
-        Ok(<[bool; NUMBER_OF_ADDRESSES]>::try_from(
-            std::iter::from_fn(|| Some(u.ratio(9, 10).unwrap_or(true)))
-                .take(NUMBER_OF_ADDRESSES)
-                .collect::<Vec<_>>()
-        ).unwrap())
-    })]
-    pub auths: [bool; NUMBER_OF_ADDRESSES],
-}
-
-#[derive(Clone, Debug, arbitrary::Arbitrary)]
+    pub auth: CallAuth,
+}
+
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
 pub struct BurnFromInput {
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(i128::MIN..=i128::MAX))]
-    pub amount: i128,
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=NUMBER_OF_ADDRESSES - 1))]
+    pub amount: Amount,
+    #[arbitrary(with = arbitrary_account_index)]
     pub spender_account_index: usize,
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=NUMBER_OF_ADDRESSES - 1))]
+    #[arbitrary(with = arbitrary_account_index)]
     pub from_account_index: usize,
-    #[arbitrary(with = |u: &mut Unstructured| {
-        // biased bool - only sometimes decline the auth
-        Ok(<[bool; NUMBER_OF_ADDRESSES]>::try_from(
-            std::iter::from_fn(|| Some(u.ratio(9, 10).unwrap_or(true)))
-                .take(NUMBER_OF_ADDRESSES)
-                .collect::<Vec<_>>()
-        ).unwrap())
-    })]
-    pub auths: [bool; NUMBER_OF_ADDRESSES],
-}
-
-#[derive(Clone, Debug, arbitrary::Arbitrary)]
+    pub auth: CallAuth,
+}
+
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
 pub struct BurnInput {
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(i128::MIN..=i128::MAX))]
-    pub amount: i128,
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=NUMBER_OF_ADDRESSES - 1))]
+    pub amount: Amount,
+    #[arbitrary(with = arbitrary_account_index)]
     pub from_account_index: usize,
-    #[arbitrary(with = |u: &mut Unstructured| {
-        // biased bool - only sometimes decline the auth
-        Ok(<[bool; NUMBER_OF_ADDRESSES]>::try_from(
-            std::iter::from_fn(|| Some(u.ratio(9, 10).unwrap_or(true)))
-                .take(NUMBER_OF_ADDRESSES)
-                .collect::<Vec<_>>()
-        ).unwrap())
-    })]
-    pub auths: [bool; NUMBER_OF_ADDRESSES],
-}
-
-#[derive(Clone, Debug, arbitrary::Arbitrary)]
+    pub auth: CallAuth,
+}
+
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
 pub struct ApproveAndTransferFromInput {
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(i128::MIN..=i128::MAX))]
-    pub amount: i128,
+    pub amount: Amount,
     #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=DAY_IN_LEDGERS * 30))]
     pub expiration_ledger: u32,
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=NUMBER_OF_ADDRESSES - 1))]
+    #[arbitrary(with = arbitrary_account_index)]
     pub from_account_index: usize,
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=NUMBER_OF_ADDRESSES - 1))]
+    #[arbitrary(with = arbitrary_account_index)]
     pub spender_account_index: usize,
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=NUMBER_OF_ADDRESSES - 1))]
+    #[arbitrary(with = arbitrary_account_index)]
     pub to_account_index: usize,
-    #[arbitrary(with = |u: &mut Unstructured| {
-        // biased bool - only sometimes decline the auth
-        Ok(<[bool; NUMBER_OF_ADDRESSES]>::try_from(
-            std::iter::from_fn(|| Some(u.ratio(9, 10).unwrap_or(true)))
-                .take(NUMBER_OF_ADDRESSES)
-                .collect::<Vec<_>>()
-        ).unwrap())
-    })]
-    pub auths: [bool; NUMBER_OF_ADDRESSES],
-}
-
-#[derive(Clone, Debug, arbitrary::Arbitrary)]
+    pub auth: CallAuth,
+}
+
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
 pub struct ApproveAndBurnFromInput {
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(i128::MIN..=i128::MAX))]
-    pub amount: i128,
+    pub amount: Amount,
     #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=DAY_IN_LEDGERS * 30))]
     pub expiration_ledger: u32,
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=NUMBER_OF_ADDRESSES - 1))]
+    #[arbitrary(with = arbitrary_account_index)]
     pub from_account_index: usize,
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=NUMBER_OF_ADDRESSES - 1))]
+    #[arbitrary(with = arbitrary_account_index)]
     pub spender_account_index: usize,
-    #[arbitrary(with = |u: &mut Unstructured| u.int_in_range(0..=NUMBER_OF_ADDRESSES - 1))]
+    #[arbitrary(with = arbitrary_account_index)]
     pub to_account_index: usize,
-    #[arbitrary(with = |u: &mut Unstructured| {
-        // biased bool - only sometimes decline the auth
-        Ok(<[bool; NUMBER_OF_ADDRESSES]>::try_from(
-            std::iter::from_fn(|| Some(u.ratio(9, 10).unwrap_or(true)))
-                .take(NUMBER_OF_ADDRESSES)
-                .collect::<Vec<_>>()
-        ).unwrap())
-    })]
-    pub auths: [bool; NUMBER_OF_ADDRESSES],
+    pub auth: CallAuth,
+}
+
+/// How a command's calls to the token are authorized and made.
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
+pub struct CallAuth {
+    /// The indexes of the accounts that don't sign the call.
+    /// The other accounts sign.
+    #[arbitrary(with = arbitrary_declined)]
+    pub declined: RustVec<usize>,
+    #[arbitrary(with = arbitrary_auth_mutation)]
+    pub auth_mutation: Option<AuthMutation>,
+    #[arbitrary(with = arbitrary_signature_expiration)]
+    pub signature_expiration: Option<SignatureExpiration>,
+    /// Call the token through a proxy contract.
+    pub via_proxy: bool,
+}
+
+/// The amount of a command,
+/// resolved against the model when the command is executed.
+///
+/// Uniformly random amounts almost never hit the boundaries
+/// of a balance or allowance, and are almost always more than
+/// the account has.
+///
+/// The balance is that of the account the amount comes from,
+/// or of the recipient of a mint.
+/// The allowance is that of the spender, or zero
+/// for commands without one.
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
+pub enum Amount {
+    Literal(#[arbitrary(with = |u: &mut Unstructured| u.int_in_range(i128::MIN..=i128::MAX))] i128),
+    Balance,
+    BalancePlusOne,
+    Allowance,
+    AllowanceMinusOne,
+    Zero,
+    One,
+    Max,
+}
+
+impl Amount {
+    /// See [`Command::VARIANTS`].
+    pub(crate) const VARIANTS: u64 = 8;
+
+    pub fn resolve(&self, balance: i128, allowance: i128) -> i128 {
+        match self {
+            Amount::Literal(amount) => *amount,
+            Amount::Balance => balance,
+            Amount::BalancePlusOne => balance.saturating_add(1),
+            Amount::Allowance => allowance,
+            Amount::AllowanceMinusOne => allowance.saturating_sub(1),
+            Amount::Zero => 0,
+            Amount::One => 1,
+            Amount::Max => i128::MAX,
+        }
+    }
+}
+
+/// A structured corruption of the authorization entry
+/// of the signer a call requires.
+///
+/// Except for `DuplicateEntry`, `ExtraEntry`,
+/// `ReuseNonce` of another signer's nonce,
+/// and `TruncateSignatures` that leaves enough signers,
+/// these should cause the call to be rejected,
+/// unless the corruption happens to leave the entry unchanged.
+///
+/// The signature mutations only apply to signers that sign with keys.
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
+pub enum AuthMutation {
+    /// Authorize a different function of the token.
+    FunctionName(u8),
+    /// Swap two of the arguments.
+    SwapArgs(u8, u8),
+    /// Replace the `i128` arguments with another amount.
+    AlterAmount(i128),
+    /// Leave out the last argument.
+    TruncateArgs,
+    /// Authorize the call on another contract.
+    ContractAddress,
+    /// Sign with a `signature_expiration_ledger` in the past.
+    ExpiredSignature,
+    /// Include the entry twice.
+    DuplicateEntry,
+    /// Include an extra entry for a call that is never made.
+    ExtraEntry,
+    /// Use the nonce of a previously accepted entry,
+    /// which may be from this signer or another.
+    ReuseNonce(u16),
+    /// Sign with another account's key instead.
+    WrongKey(u8),
+    /// Flip one bit of the signatures.
+    FlipSignatureBit(u16),
+    /// Leave out the last signature.
+    TruncateSignatures,
+    /// Pair a signature with another account's public key.
+    WrongPublicKey(u8, u8),
+    /// Include one of the signatures twice.
+    DuplicateSignature(u8),
+    /// Sign for another network.
+    OtherNetwork(u8),
+}
+
+impl AuthMutation {
+    /// See [`Command::VARIANTS`].
+    pub(crate) const VARIANTS: u64 = 15;
+}
+
+fn arbitrary_account_index(u: &mut Unstructured) -> arbitrary::Result<usize> {
+    u.int_in_range(0..=ACCOUNT_INDEX_RANGE - 1)
+}
+
+fn arbitrary_declined(u: &mut Unstructured) -> arbitrary::Result<RustVec<usize>> {
+    let mut declined = RustVec::new();
+    // biased - only sometimes decline the auth,
+    // and stop once the input runs out
+    while declined.len() < MAX_NUMBER_OF_ADDRESSES && !u.ratio(3, 4)? {
+        declined.push(arbitrary_account_index(u)?);
+    }
+    Ok(declined)
+}
+
+fn arbitrary_auth_mutation(u: &mut Unstructured) -> arbitrary::Result<Option<AuthMutation>> {
+    // only sometimes corrupt the auths
+    if u.ratio(1, 10)? {
+        Ok(Some(u.arbitrary()?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// The `signature_expiration_ledger` of the authorization entries,
+/// relative to the current ledger.
+///
+/// The host accepts expirations from the current ledger
+/// up to `max_entry_ttl - 1` ledgers after it.
+/// Without one, entries expire at the end of that range.
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
+pub enum SignatureExpiration {
+    /// This many ledgers before the current ledger, plus one.
+    Expired(u16),
+    /// The current ledger.
+    CurrentLedger,
+    /// This many ledgers after the current ledger.
+    After(u32),
+    /// This many ledgers before the last accepted ledger.
+    InsideMaxTtl(u16),
+    /// This many ledgers after the last accepted ledger, plus one.
+    BeyondMaxTtl(u16),
+}
+
+impl SignatureExpiration {
+    /// See [`Command::VARIANTS`].
+    pub(crate) const VARIANTS: u64 = 5;
+}
+
+fn arbitrary_signature_expiration(
+    u: &mut Unstructured,
+) -> arbitrary::Result<Option<SignatureExpiration>> {
+    // mostly sign with the longest valid expiration
+    if u.ratio(1, 10)? {
+        Ok(Some(u.arbitrary()?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Replay a previously accepted authorization entry,
+/// with the same nonce, signature and invocation.
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
+pub struct ReplayAuthInput {
+    /// Which entry to replay, modulo the number of accepted entries.
+    pub accepted_index: u16,
+}
+
+/// Introduce a new address, with no balance or allowances,
+/// that later commands may use.
+///
+/// Ignored once there are [`MAX_NUMBER_OF_ADDRESSES`].
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
+pub struct FreshAddressInput {
+    pub address_type: AddressType,
+}
+
+/// A token-specific command from `ContractTokenOps::extension_commands`.
+#[derive(Clone, Debug, PartialEq, arbitrary::Arbitrary, Serialize, Deserialize)]
+pub struct ExtensionInput {
+    /// Which command to run, modulo the number of commands.
+    pub command_index: u8,
+    /// The bytes the command's payload is generated from.
+    pub payload: RustVec<u8>,
 }
 
 impl ApproveAndTransferFromInput {
-    pub fn to_approve_input(&self) -> ApproveInput {
+    /// The approval, with `amount` resolved from `self.amount`
+    /// before it, so that both halves use the same amount.
+    pub fn to_approve_input(&self, amount: i128) -> ApproveInput {
         ApproveInput {
-            amount: self.amount,
+            amount: Amount::Literal(amount),
             expiration_ledger: self.expiration_ledger,
             from_account_index: self.from_account_index,
             spender_account_index: self.spender_account_index,
-            auths: self.auths,
+            auth: self.auth.clone(),
         }
     }
 
-    pub fn to_transfer_from_input(&self) -> TransferFromInput {
+    pub fn to_transfer_from_input(&self, amount: i128) -> TransferFromInput {
         TransferFromInput {
-            amount: self.amount,
+            amount: Amount::Literal(amount),
             spender_account_index: self.spender_account_index,
             from_account_index: self.from_account_index,
             to_account_index: self.to_account_index,
-            auths: self.auths,
+            auth: self.auth.clone(),
         }
     }
 }
 
 impl ApproveAndBurnFromInput {
-    pub fn to_approve_input(&self) -> ApproveInput {
+    /// The approval, with `amount` resolved from `self.amount`
+    /// before it, so that both halves use the same amount.
+    pub fn to_approve_input(&self, amount: i128) -> ApproveInput {
         ApproveInput {
-            amount: self.amount,
+            amount: Amount::Literal(amount),
             expiration_ledger: self.expiration_ledger,
             from_account_index: self.from_account_index,
             spender_account_index: self.spender_account_index,
-            auths: self.auths,
+            auth: self.auth.clone(),
         }
     }
 
-    pub fn to_burn_from_input(&self) -> BurnFromInput {
+    pub fn to_burn_from_input(&self, amount: i128) -> BurnFromInput {
         BurnFromInput {
-            amount: self.amount,
+            amount: Amount::Literal(amount),
             spender_account_index: self.spender_account_index,
             from_account_index: self.from_account_index,
-            auths: self.auths,
+            auth: self.auth.clone(),
+        }
+    }
+}
+
+impl Command {
+    /// Take the command's account indexes modulo the number of accounts.
+    pub fn wrap_account_indexes(&mut self, number_of_accounts: usize) {
+        let wrap = |index: &mut usize| *index %= number_of_accounts;
+        match self {
+            Command::Mint(input) => wrap(&mut input.to_account_index),
+            Command::Approve(input) => {
+                wrap(&mut input.from_account_index);
+                wrap(&mut input.spender_account_index);
+            }
+            Command::TransferFrom(input) => {
+                wrap(&mut input.spender_account_index);
+                wrap(&mut input.from_account_index);
+                wrap(&mut input.to_account_index);
+            }
+            Command::Transfer(input) => {
+                wrap(&mut input.from_account_index);
+                wrap(&mut input.to_account_index);
+            }
+            Command::BurnFrom(input) => {
+                wrap(&mut input.spender_account_index);
+                wrap(&mut input.from_account_index);
+            }
+            Command::Burn(input) => wrap(&mut input.from_account_index),
+            Command::ApproveAndTransferFrom(input) => {
+                wrap(&mut input.from_account_index);
+                wrap(&mut input.spender_account_index);
+                wrap(&mut input.to_account_index);
+            }
+            Command::ApproveAndBurnFrom(input) => {
+                wrap(&mut input.from_account_index);
+                wrap(&mut input.spender_account_index);
+                wrap(&mut input.to_account_index);
+            }
+            Command::Extension(_) | Command::ReplayAuth(_) | Command::FreshAddress(_) => {}
+        }
+        if let Some(auth) = self.auth_mut() {
+            auth.declined.iter_mut().for_each(wrap);
+        }
+    }
+
+    /// The command's amount, if it has one.
+    pub fn amount_mut(&mut self) -> Option<&mut Amount> {
+        match self {
+            Command::Mint(input) => Some(&mut input.amount),
+            Command::Approve(input) => Some(&mut input.amount),
+            Command::TransferFrom(input) => Some(&mut input.amount),
+            Command::Transfer(input) => Some(&mut input.amount),
+            Command::BurnFrom(input) => Some(&mut input.amount),
+            Command::Burn(input) => Some(&mut input.amount),
+            Command::ApproveAndTransferFrom(input) => Some(&mut input.amount),
+            Command::ApproveAndBurnFrom(input) => Some(&mut input.amount),
+            Command::Extension(_) | Command::ReplayAuth(_) | Command::FreshAddress(_) => None,
+        }
+    }
+
+    /// How the command's calls are authorized, if it makes any.
+    pub fn auth_mut(&mut self) -> Option<&mut CallAuth> {
+        match self {
+            Command::Mint(input) => Some(&mut input.auth),
+            Command::Approve(input) => Some(&mut input.auth),
+            Command::TransferFrom(input) => Some(&mut input.auth),
+            Command::Transfer(input) => Some(&mut input.auth),
+            Command::BurnFrom(input) => Some(&mut input.auth),
+            Command::Burn(input) => Some(&mut input.auth),
+            Command::ApproveAndTransferFrom(input) => Some(&mut input.auth),
+            Command::ApproveAndBurnFrom(input) => Some(&mut input.auth),
+            Command::Extension(_) | Command::ReplayAuth(_) | Command::FreshAddress(_) => None,
         }
     }
 }
@@ -1,12 +1,34 @@
+mod accounts;
 pub mod addrgen;
+mod artifact;
 pub mod config;
 pub mod fuzz;
 pub mod input;
+mod macros;
+pub mod model;
+mod mutator;
+pub mod test_support;
+mod testgen;
 pub mod util;
 
-pub use config::{Config, ContractTokenOps, TokenAdminClient};
-pub use fuzz::fuzz_token;
+pub use accounts::AccountContract;
+pub use addrgen::TestSigner;
+pub use config::{
+    ArgSpec, ArgValues, Config, ContractTokenOps, ErrorCodes, ExtensionCommand, FnSpec, Invariant,
+    TokenAdminClient, TokenContractResult,
+};
+pub use fuzz::{fuzz_token, fuzz_token_differential};
 pub use input::Input;
+pub use model::{ContractState, TokenModel};
+pub use mutator::{crossover_inputs, mutate_input};
+pub use testgen::{generate_test, TestSetup};
+
+/// Used by [`token_fuzz_target!`] and [`token_fuzz_mutator!`].
+#[doc(hidden)]
+pub mod __private {
+    pub use libfuzzer_sys;
+    pub use soroban_sdk;
+}
 
 // copied from somewhere in the sdk
 const DAY_IN_LEDGERS: u32 = 17280;
@@ -0,0 +1,200 @@
+/// Define a fuzz target for a token contract written in Rust.
+///
+/// This expands to a `fuzz_target!` that calls [`fuzz_token`](crate::fuzz_token)
+/// with a [`Config`](crate::Config) for the token, along with the
+/// [`ContractTokenOps`](crate::ContractTokenOps) and
+/// [`TokenAdminClient`](crate::TokenAdminClient) implementations it needs.
+///
+/// It takes the contract type, the contract's generated client type,
+/// and a closure that initializes the contract with the admin address.
+/// The admin client calls the generated client's `mint` method.
+///
+/// As with `fuzz_target!`, the file defining the fuzz target
+/// must be `#![no_main]`, and its crate must depend on `libfuzzer-sys`,
+/// which the expansion of `fuzz_target!` refers to by name.
+///
+/// ```no_run
+/// #![no_main]
+///
+/// use soroban_sdk::String;
+///
+/// # // A stand-in for the example token crate.
+/// # mod example_token {
+/// #     use soroban_sdk::{contract, contractimpl, Address, Env, String};
+/// #     pub mod contract {
+/// #         pub use super::Token;
+/// #     }
+/// #     #[contract]
+/// #     pub struct Token;
+/// #     #[contractimpl]
+/// #     impl Token {
+/// #         pub fn initialize(_e: Env, _a: Address, _d: u32, _n: String, _s: String) {}
+/// #         pub fn mint(_e: Env, _to: Address, _amount: i128) {}
+/// #     }
+/// # }
+/// soroban_token_fuzzer::token_fuzz_target!(
+///     example_token::contract::Token,
+///     example_token::TokenClient,
+///     |env, client, admin| {
+///         let name = String::from_str(env, "token");
+///         let symbol = String::from_str(env, "TKN");
+///         client.initialize(admin, &10, &name, &symbol);
+///     }
+/// );
+/// # // Keep rustdoc from wrapping the example in a `main`.
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! token_fuzz_target {
+    ($contract:expr, $($client:ident)::+, $init:expr $(,)?) => {
+        // Keep the generated items out of the caller's namespace.
+        const _: () = {
+            $crate::__private::libfuzzer_sys::fuzz_target!(
+                |input: $crate::Input| -> $crate::__private::libfuzzer_sys::Corpus {
+                    let config = $crate::Config::contract(TokenOps);
+                    $crate::fuzz_token(config, input)
+                }
+            );
+
+            struct TokenOps;
+
+            struct AdminClient<'a> {
+                client: $($client)::+<'a>,
+            }
+
+            impl $crate::ContractTokenOps for TokenOps {
+                fn register_contract_init(
+                    &self,
+                    env: &$crate::__private::soroban_sdk::Env,
+                    admin: &$crate::__private::soroban_sdk::Address,
+                ) -> $crate::__private::soroban_sdk::Address {
+                    fn init_fn<F>(f: F) -> F
+                    where
+                        F: FnOnce(
+                            &$crate::__private::soroban_sdk::Env,
+                            &$($client)::+<'_>,
+                            &$crate::__private::soroban_sdk::Address,
+                        ),
+                    {
+                        f
+                    }
+
+                    let token_contract_id = env.register_contract(None, $contract);
+                    let client = <$($client)::+>::new(env, &token_contract_id);
+                    init_fn($init)(env, &client, admin);
+
+                    token_contract_id
+                }
+
+                fn reregister_contract(
+                    &self,
+                    env: &$crate::__private::soroban_sdk::Env,
+                    token_contract_id: &$crate::__private::soroban_sdk::Address,
+                ) {
+                    env.register_contract(Some(token_contract_id), $contract);
+                }
+
+                fn new_admin_client<'a>(
+                    &self,
+                    env: &$crate::__private::soroban_sdk::Env,
+                    token_contract_id: &$crate::__private::soroban_sdk::Address,
+                ) -> Box<dyn $crate::TokenAdminClient<'a> + 'a> {
+                    Box::new(AdminClient {
+                        client: <$($client)::+>::new(env, token_contract_id),
+                    })
+                }
+            }
+
+            impl<'a> $crate::TokenAdminClient<'a> for AdminClient<'a> {
+                fn try_mint(
+                    &self,
+                    to: &$crate::__private::soroban_sdk::Address,
+                    amount: &i128,
+                ) -> $crate::TokenContractResult {
+                    self.client.try_mint(to, amount)
+                }
+
+                fn set_auths<'b>(
+                    &self,
+                    auths: &'b [$crate::__private::soroban_sdk::xdr::SorobanAuthorizationEntry],
+                ) -> Box<dyn $crate::TokenAdminClient<'b> + 'b> {
+                    Box::new(AdminClient {
+                        client: <$($client)::+>::new(&self.client.env, &self.client.address)
+                            .set_auths(auths),
+                    })
+                }
+            }
+        };
+    };
+}
+
+/// Define a structure-aware custom mutator and crossover
+/// for the fuzz target in the same file.
+///
+/// This expands to a `fuzz_mutator!` that calls
+/// [`mutate_input`](crate::mutate_input),
+/// and an `LLVMFuzzerCustomCrossOver` that calls
+/// [`crossover_inputs`](crate::crossover_inputs).
+/// They mutate decoded `Input`s, inserting, deleting,
+/// duplicating and swapping commands,
+/// splitting and merging transactions,
+/// and changing ledger advances, signers and amounts,
+/// instead of the bytes they are generated from.
+///
+/// ```no_run
+/// #![no_main]
+///
+/// # // A stand-in for the example token crate.
+/// # mod example_token {
+/// #     use soroban_sdk::{contract, contractimpl, Address, Env, String};
+/// #     pub mod contract {
+/// #         pub use super::Token;
+/// #     }
+/// #     #[contract]
+/// #     pub struct Token;
+/// #     #[contractimpl]
+/// #     impl Token {
+/// #         pub fn initialize(_e: Env, _a: Address, _d: u32, _n: String, _s: String) {}
+/// #         pub fn mint(_e: Env, _to: Address, _amount: i128) {}
+/// #     }
+/// # }
+/// # soroban_token_fuzzer::token_fuzz_target!(
+/// #     example_token::contract::Token,
+/// #     example_token::TokenClient,
+/// #     |_, _, _| {}
+/// # );
+/// # /*
+/// soroban_token_fuzzer::token_fuzz_target!(/* ... */);
+/// # */
+/// soroban_token_fuzzer::token_fuzz_mutator!();
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! token_fuzz_mutator {
+    () => {
+        const _: () = {
+            $crate::__private::libfuzzer_sys::fuzz_mutator!(
+                |data: &mut [u8], size: usize, max_size: usize, seed: u32| {
+                    $crate::mutate_input(data, size, max_size, seed)
+                }
+            );
+
+            /// Auto-generated function.
+            #[export_name = "LLVMFuzzerCustomCrossOver"]
+            extern "C" fn rust_fuzzer_custom_crossover(
+                data1: *const u8,
+                size1: usize,
+                data2: *const u8,
+                size2: usize,
+                out: *mut u8,
+                max_out_size: usize,
+                seed: ::std::os::raw::c_uint,
+            ) -> usize {
+                let data1 = unsafe { ::std::slice::from_raw_parts(data1, size1) };
+                let data2 = unsafe { ::std::slice::from_raw_parts(data2, size2) };
+                let out = unsafe { ::std::slice::from_raw_parts_mut(out, max_out_size) };
+                $crate::crossover_inputs(data1, data2, out, seed as u32)
+            }
+        };
+    };
+}
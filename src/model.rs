@@ -0,0 +1,184 @@
+use crate::util::*;
+use num_bigint::BigInt;
+use soroban_sdk::Address;
+use std::collections::BTreeMap;
+use std::vec::Vec as RustVec;
+
+/// The fuzzer's model of what the token's internal state should be.
+///
+/// After every successful call to the token,
+/// the fuzzer applies the same operation to the model,
+/// and at the end of every transaction it asserts that
+/// the token agrees with the model.
+///
+/// The default model is [`ContractState`], which follows
+/// the economics of the example token.
+/// Tokens that behave differently can provide their own
+/// with [`Config::with_model`](crate::Config::with_model).
+///
+/// Models live across transactions, and each transaction
+/// has a new `Env`, so they can't hold on to anything containing an `Env`.
+pub trait TokenModel {
+    /// `to` received `amount` newly minted tokens.
+    fn mint(&mut self, to: &Address, amount: i128);
+
+    /// `from` allowed `spender` to spend `amount` until `expiration_ledger`.
+    fn approve(&mut self, from: &Address, spender: &Address, amount: i128, expiration_ledger: u32);
+
+    /// `from` transferred `amount` to `to`.
+    fn transfer(&mut self, from: &Address, to: &Address, amount: i128);
+
+    /// `spender` transferred `amount` from `from` to `to`.
+    fn transfer_from(&mut self, spender: &Address, from: &Address, to: &Address, amount: i128);
+
+    /// `from` burned `amount`.
+    fn burn(&mut self, from: &Address, amount: i128);
+
+    /// `spender` burned `amount` from `from`.
+    fn burn_from(&mut self, spender: &Address, from: &Address, amount: i128);
+
+    /// The allowance from `from` to `spender` expired.
+    ///
+    /// This is called after time advances
+    /// for allowances the token reports as zero
+    /// that the model doesn't.
+    fn expire_allowance(&mut self, from: &Address, spender: &Address);
+
+    /// The expected balance of `addr`.
+    fn balance(&self, addr: &Address) -> i128;
+
+    /// The expected allowance from `from` to `spender`.
+    fn allowance(&self, from: &Address, spender: &Address) -> i128;
+
+    /// The expected sum of all balances.
+    fn total_supply(&self) -> BigInt;
+}
+
+/// This tracks what we believe is true about the internal contract state.
+///
+/// We mirror calculations about balances etc that we expect the contract
+/// is making, which means we will be wrong if the token implements economics
+/// that differ from the example token.
+///
+/// This kind of state mirroring I would not generally do in a fuzz test
+/// but since the token interface is small and it can be used to test that
+/// multiple implementations behave in similar ways, I think it is worth
+/// the potential maintenance brittleness.
+///
+/// Since this state is persistent across transactions,
+/// it can not store anything containing an `Env`. Instead
+/// it can contain accessors that instantiate various contract
+/// types from any `Env`.
+#[derive(Default)]
+pub struct ContractState {
+    balances: BTreeMap<RustVec<u8>, i128>,
+    allowances: BTreeMap<(RustVec<u8>, RustVec<u8>), i128>, // (from, spender)
+    sum_of_mints: BigInt,
+    sum_of_burns: BigInt,
+}
+
+impl ContractState {
+    pub fn init() -> Self {
+        ContractState::default()
+    }
+
+    fn get_balance(&self, addr: &Address) -> i128 {
+        let addr_bytes = address_to_bytes(addr);
+        self.balances.get(&addr_bytes).copied().unwrap_or(0)
+    }
+
+    fn sub_balance(&mut self, addr: &Address, amount: i128) {
+        let addr_bytes = address_to_bytes(addr);
+        let balance = self.get_balance(addr);
+        let new_balance = balance.checked_sub(amount).expect("overflow");
+        assert!(new_balance >= 0);
+        self.balances.insert(addr_bytes, new_balance);
+    }
+
+    fn add_balance(&mut self, addr: &Address, amount: i128) {
+        let addr_bytes = address_to_bytes(addr);
+        let balance = self.get_balance(addr);
+        let new_balance = balance.checked_add(amount).expect("overflow");
+        assert!(new_balance >= 0);
+        self.balances.insert(addr_bytes, new_balance);
+    }
+
+    fn set_allowance(&mut self, from: &Address, spender: &Address, amount: i128) {
+        assert!(amount >= 0);
+        let from_bytes = address_to_bytes(from);
+        let spender_bytes = address_to_bytes(spender);
+        self.allowances.insert((from_bytes, spender_bytes), amount);
+    }
+
+    fn get_allowance(&self, from: &Address, spender: &Address) -> i128 {
+        let from_bytes = address_to_bytes(from);
+        let spender_bytes = address_to_bytes(spender);
+        self.allowances
+            .get(&(from_bytes, spender_bytes))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn sub_allowance(&mut self, from: &Address, spender: &Address, amount: i128) {
+        let allowance = self.get_allowance(from, spender);
+        let new_allowance = allowance.checked_sub(amount).expect("overflow");
+        assert!(new_allowance >= 0);
+        self.set_allowance(from, spender, new_allowance);
+    }
+}
+
+impl TokenModel for ContractState {
+    fn mint(&mut self, to: &Address, amount: i128) {
+        self.add_balance(to, amount);
+        self.sum_of_mints += amount;
+    }
+
+    fn approve(
+        &mut self,
+        from: &Address,
+        spender: &Address,
+        amount: i128,
+        _expiration_ledger: u32,
+    ) {
+        // fixme track expiration ledger instead of asking the contract
+        self.set_allowance(from, spender, amount);
+    }
+
+    fn transfer(&mut self, from: &Address, to: &Address, amount: i128) {
+        self.sub_balance(from, amount);
+        self.add_balance(to, amount);
+    }
+
+    fn transfer_from(&mut self, spender: &Address, from: &Address, to: &Address, amount: i128) {
+        self.sub_balance(from, amount);
+        self.add_balance(to, amount);
+        self.sub_allowance(from, spender, amount);
+    }
+
+    fn burn(&mut self, from: &Address, amount: i128) {
+        self.sub_balance(from, amount);
+        self.sum_of_burns += amount;
+    }
+
+    fn burn_from(&mut self, spender: &Address, from: &Address, amount: i128) {
+        self.sub_balance(from, amount);
+        self.sub_allowance(from, spender, amount);
+        self.sum_of_burns += amount;
+    }
+
+    fn expire_allowance(&mut self, from: &Address, spender: &Address) {
+        self.set_allowance(from, spender, 0);
+    }
+
+    fn balance(&self, addr: &Address) -> i128 {
+        self.get_balance(addr)
+    }
+
+    fn allowance(&self, from: &Address, spender: &Address) -> i128 {
+        self.get_allowance(from, spender)
+    }
+
+    fn total_supply(&self) -> BigInt {
+        &self.sum_of_mints - &self.sum_of_burns
+    }
+}
@@ -0,0 +1,432 @@
+//! A structure-aware mutator and crossover for libFuzzer.
+//!
+//! libFuzzer mutates the raw bytes an `Input` is generated from,
+//! and with `arbitrary` one changed byte early on
+//! can reshape every transaction after it.
+//! These decode the input, change one thing about its structure,
+//! and encode it again with `Input::to_artifact`,
+//! so the rest of the input is preserved.
+//!
+//! Some of the time, and for bytes that don't decode,
+//! the mutator defers to libFuzzer's own.
+
+use crate::input::*;
+use crate::DAY_IN_LEDGERS;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use soroban_sdk::testutils::arbitrary::arbitrary::{Arbitrary, Unstructured};
+use std::vec::Vec as RustVec;
+
+/// How many of the mutations are left to libFuzzer, out of 4.
+const DEFAULT_MUTATION_RATIO: u32 = 1;
+
+/// The bytes a new command is generated from.
+const COMMAND_BYTES: usize = 64;
+
+/// Mutate the artifact in `data[..size]`, as `fuzz_mutator!` does,
+/// returning the size of the mutated artifact.
+pub fn mutate_input(data: &mut [u8], size: usize, max_size: usize, seed: u32) -> usize {
+    let mut rng = StdRng::seed_from_u64(seed.into());
+
+    if rng.gen_ratio(DEFAULT_MUTATION_RATIO, 4) {
+        return libfuzzer_sys::fuzzer_mutate(data, size, max_size);
+    }
+    let Ok(mut input) = Input::from_artifact(&data[..size]) else {
+        return libfuzzer_sys::fuzzer_mutate(data, size, max_size);
+    };
+
+    mutate(&mut rng, &mut input);
+
+    match input.to_artifact() {
+        Ok(bytes) if bytes.len() <= max_size => {
+            data[..bytes.len()].copy_from_slice(&bytes);
+            bytes.len()
+        }
+        _ => libfuzzer_sys::fuzzer_mutate(data, size, max_size),
+    }
+}
+
+/// Splice the transactions of the artifacts `data1` and `data2` into `out`,
+/// as `LLVMFuzzerCustomCrossOver` does,
+/// returning the size of the new artifact.
+///
+/// The new input has the addresses of the first,
+/// and transactions from the start of the first
+/// followed by transactions from the end of the second.
+/// If the inputs can't be crossed, the first artifact is copied as it is.
+pub fn crossover_inputs(data1: &[u8], data2: &[u8], out: &mut [u8], seed: u32) -> usize {
+    let mut rng = StdRng::seed_from_u64(seed.into());
+
+    let crossed = match (Input::from_artifact(data1), Input::from_artifact(data2)) {
+        (Ok(input1), Ok(input2)) => crossover(&mut rng, input1, &input2, out.len()),
+        _ => None,
+    };
+    copy_artifact(crossed.as_deref().unwrap_or(data1), out)
+}
+
+/// The artifact of the crossed inputs,
+/// dropping transactions from the end until it fits in `max_size`,
+/// or `None` if it can't be encoded.
+fn crossover(
+    rng: &mut StdRng,
+    input1: Input,
+    input2: &Input,
+    max_size: usize,
+) -> Option<RustVec<u8>> {
+    let split1 = rng.gen_range(0..=input1.transactions.len());
+    let split2 = rng.gen_range(0..=input2.transactions.len());
+    let mut input = Input {
+        address_generator: input1.address_generator,
+        transactions: input1.transactions[..split1]
+            .iter()
+            .chain(&input2.transactions[split2..])
+            .cloned()
+            .collect(),
+    };
+
+    loop {
+        let bytes = input.to_artifact().ok()?;
+        if bytes.len() <= max_size || input.transactions.is_empty() {
+            return Some(bytes);
+        }
+        input.transactions.pop();
+    }
+}
+
+/// Copy as much of an artifact as fits into `out`,
+/// returning the size copied.
+fn copy_artifact(data: &[u8], out: &mut [u8]) -> usize {
+    let size = data.len().min(out.len());
+    out[..size].copy_from_slice(&data[..size]);
+    size
+}
+
+#[derive(Clone, Copy)]
+enum Mutation {
+    InsertCommand,
+    DeleteCommand,
+    DuplicateCommand,
+    SwapCommands,
+    SplitTransaction,
+    MergeTransactions,
+    TweakAdvanceLedgers,
+    ToggleAuth,
+    NudgeAmount,
+}
+
+const MUTATIONS: &[Mutation] = &[
+    Mutation::InsertCommand,
+    Mutation::DeleteCommand,
+    Mutation::DuplicateCommand,
+    Mutation::SwapCommands,
+    Mutation::SplitTransaction,
+    Mutation::MergeTransactions,
+    Mutation::TweakAdvanceLedgers,
+    Mutation::ToggleAuth,
+    Mutation::NudgeAmount,
+];
+
+/// Apply one random mutation.
+fn mutate(rng: &mut StdRng, input: &mut Input) {
+    let mutation = MUTATIONS[rng.gen_range(0..MUTATIONS.len())];
+    apply_mutation(rng, input, mutation);
+}
+
+/// Apply `mutation`,
+/// or insert a command if it doesn't apply to the input.
+fn apply_mutation(rng: &mut StdRng, input: &mut Input, mutation: Mutation) {
+    let mutated = match mutation {
+        Mutation::InsertCommand => false,
+        Mutation::DeleteCommand => delete_command(rng, input),
+        Mutation::DuplicateCommand => duplicate_command(rng, input),
+        Mutation::SwapCommands => swap_commands(rng, input),
+        Mutation::SplitTransaction => split_transaction(rng, input),
+        Mutation::MergeTransactions => merge_transactions(rng, input),
+        Mutation::TweakAdvanceLedgers => tweak_advance_ledgers(rng, input),
+        Mutation::ToggleAuth => toggle_auth(rng, input),
+        Mutation::NudgeAmount => nudge_amount(rng, input),
+    };
+
+    if !mutated {
+        insert_command(rng, input);
+    }
+}
+
+/// The (transaction, command) index of every command.
+fn command_positions(input: &Input) -> RustVec<(usize, usize)> {
+    input
+        .transactions
+        .iter()
+        .enumerate()
+        .flat_map(|(tx_index, tx)| (0..tx.commands.len()).map(move |i| (tx_index, i)))
+        .collect()
+}
+
+fn random_command_position(rng: &mut StdRng, input: &Input) -> Option<(usize, usize)> {
+    let positions = command_positions(input);
+    if positions.is_empty() {
+        return None;
+    }
+    Some(positions[rng.gen_range(0..positions.len())])
+}
+
+/// A random command that a command could be inserted before,
+/// adding a transaction if there are none.
+fn random_insert_position(rng: &mut StdRng, input: &mut Input) -> (usize, usize) {
+    if input.transactions.is_empty() {
+        input.transactions.push(Transaction {
+            commands: vec![],
+            advance_ledgers: 1,
+            switch_network: false,
+        });
+    }
+    let tx_index = rng.gen_range(0..input.transactions.len());
+    let index = rng.gen_range(0..=input.transactions[tx_index].commands.len());
+    (tx_index, index)
+}
+
+fn insert_command(rng: &mut StdRng, input: &mut Input) {
+    let mut bytes = [0; COMMAND_BYTES];
+    rng.fill(&mut bytes[..]);
+    let Ok(command) = Command::arbitrary(&mut Unstructured::new(&bytes)) else {
+        return;
+    };
+
+    let (tx_index, index) = random_insert_position(rng, input);
+    input.transactions[tx_index].commands.insert(index, command);
+}
+
+fn delete_command(rng: &mut StdRng, input: &mut Input) -> bool {
+    let Some((tx_index, index)) = random_command_position(rng, input) else {
+        return false;
+    };
+    input.transactions[tx_index].commands.remove(index);
+    true
+}
+
+fn duplicate_command(rng: &mut StdRng, input: &mut Input) -> bool {
+    let Some((tx_index, index)) = random_command_position(rng, input) else {
+        return false;
+    };
+    let command = input.transactions[tx_index].commands[index].clone();
+    let (tx_index, index) = random_insert_position(rng, input);
+    input.transactions[tx_index].commands.insert(index, command);
+    true
+}
+
+fn swap_commands(rng: &mut StdRng, input: &mut Input) -> bool {
+    let positions = command_positions(input);
+    if positions.len() < 2 {
+        return false;
+    }
+    let (tx_index1, index1) = positions[rng.gen_range(0..positions.len())];
+    let (tx_index2, index2) = positions[rng.gen_range(0..positions.len())];
+    let command1 = input.transactions[tx_index1].commands[index1].clone();
+    let command2 = std::mem::replace(
+        &mut input.transactions[tx_index2].commands[index2],
+        command1,
+    );
+    input.transactions[tx_index1].commands[index1] = command2;
+    true
+}
+
+/// Split a transaction in two, so that the ledger advances partway through it.
+fn split_transaction(rng: &mut StdRng, input: &mut Input) -> bool {
+    let Some((tx_index, index)) = random_command_position(rng, input) else {
+        return false;
+    };
+    let tx = &mut input.transactions[tx_index];
+    let commands = tx.commands.split_off(index);
+    let second = Transaction {
+        commands,
+        advance_ledgers: tx.advance_ledgers,
+        switch_network: tx.switch_network,
+    };
+    tx.advance_ledgers = random_advance_ledgers(rng);
+    tx.switch_network = false;
+    input.transactions.insert(tx_index + 1, second);
+    true
+}
+
+/// Merge a transaction into the one before it.
+fn merge_transactions(rng: &mut StdRng, input: &mut Input) -> bool {
+    if input.transactions.len() < 2 {
+        return false;
+    }
+    let tx_index = rng.gen_range(1..input.transactions.len());
+    let tx = input.transactions.remove(tx_index);
+    let prev = &mut input.transactions[tx_index - 1];
+    prev.commands.extend(tx.commands);
+    prev.advance_ledgers = tx.advance_ledgers;
+    prev.switch_network = tx.switch_network;
+    true
+}
+
+fn tweak_advance_ledgers(rng: &mut StdRng, input: &mut Input) -> bool {
+    if input.transactions.is_empty() {
+        return false;
+    }
+    let tx_index = rng.gen_range(0..input.transactions.len());
+    let advance_ledgers = &mut input.transactions[tx_index].advance_ledgers;
+    *advance_ledgers = match rng.gen_range(0..4) {
+        0 => advance_ledgers.saturating_sub(1).max(1),
+        1 => advance_ledgers.saturating_add(1).min(DAY_IN_LEDGERS),
+        2 => *[1, DAY_IN_LEDGERS].choose(rng).unwrap(),
+        _ => random_advance_ledgers(rng),
+    };
+    true
+}
+
+fn random_advance_ledgers(rng: &mut StdRng) -> u32 {
+    rng.gen_range(1..=DAY_IN_LEDGERS)
+}
+
+/// Make one more account decline to sign a command's calls, or one fewer.
+fn toggle_auth(rng: &mut StdRng, input: &mut Input) -> bool {
+    let Some(auth) = random_command_field(rng, input, Command::auth_mut) else {
+        return false;
+    };
+    let declined = &mut auth.declined;
+    if !declined.is_empty() && (declined.len() == MAX_NUMBER_OF_ADDRESSES || rng.gen()) {
+        declined.remove(rng.gen_range(0..declined.len()));
+    } else {
+        declined.push(rng.gen_range(0..ACCOUNT_INDEX_RANGE));
+    }
+    true
+}
+
+/// Move an amount a little, or to one relative to the model.
+fn nudge_amount(rng: &mut StdRng, input: &mut Input) -> bool {
+    let Some(amount) = random_command_field(rng, input, Command::amount_mut) else {
+        return false;
+    };
+    *amount = match (&*amount, rng.gen_range(0..4)) {
+        (Amount::Literal(n), 0) => Amount::Literal(n.saturating_add(rng.gen_range(1..=16))),
+        (Amount::Literal(n), 1) => Amount::Literal(n.saturating_sub(rng.gen_range(1..=16))),
+        (Amount::Literal(n), 2) => Amount::Literal(n.saturating_neg()),
+        _ => [
+            Amount::Balance,
+            Amount::BalancePlusOne,
+            Amount::Allowance,
+            Amount::AllowanceMinusOne,
+            Amount::Zero,
+            Amount::One,
+            Amount::Max,
+        ]
+        .choose(rng)
+        .unwrap()
+        .clone(),
+    };
+    true
+}
+
+/// A field of a random command that has one.
+fn random_command_field<'a, T>(
+    rng: &mut StdRng,
+    input: &'a mut Input,
+    field: fn(&mut Command) -> Option<&mut T>,
+) -> Option<&'a mut T> {
+    let mut fields: RustVec<&mut T> = input
+        .transactions
+        .iter_mut()
+        .flat_map(|tx| tx.commands.iter_mut())
+        .filter_map(field)
+        .collect();
+    if fields.is_empty() {
+        return None;
+    }
+    let index = rng.gen_range(0..fields.len());
+    Some(fields.swap_remove(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_bytes(rng: &mut StdRng) -> RustVec<u8> {
+        let mut bytes = vec![0; rng.gen_range(0..2048)];
+        rng.fill(&mut bytes[..]);
+        bytes
+    }
+
+    fn arbitrary_inputs() -> RustVec<Input> {
+        let mut rng = StdRng::seed_from_u64(0);
+        (0..200)
+            .filter_map(|_| Input::from_artifact(&random_bytes(&mut rng)).ok())
+            .collect()
+    }
+
+    #[test]
+    fn mutations_keep_inputs_decodable() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for input in arbitrary_inputs() {
+            for mutation in MUTATIONS {
+                let mut mutated = input.clone();
+                apply_mutation(&mut rng, &mut mutated, *mutation);
+                let artifact = mutated.to_artifact().unwrap();
+                assert_eq!(Input::from_artifact(&artifact).unwrap(), mutated);
+            }
+        }
+    }
+
+    #[test]
+    fn mutated_artifacts_fit() {
+        for (seed, input) in (0..).zip(arbitrary_inputs()) {
+            // libFuzzer's own mutator can only run while fuzzing,
+            // so skip the seeds that defer to it,
+            // and check that the mutation encodes before `mutate_input` does.
+            let mut rng = StdRng::seed_from_u64(seed.into());
+            if rng.gen_ratio(DEFAULT_MUTATION_RATIO, 4) {
+                continue;
+            }
+            let mut mutated = input.clone();
+            mutate(&mut rng, &mut mutated);
+            let mutated = mutated.to_artifact().unwrap();
+
+            let artifact = input.to_artifact().unwrap();
+            let max_size = artifact.len() + 1024;
+            let mut data = artifact.clone();
+            data.resize(max_size, 0);
+            let size = mutate_input(&mut data, artifact.len(), max_size, seed);
+            assert!(size <= max_size);
+            assert_eq!(data[..size], mutated[..]);
+        }
+    }
+
+    #[test]
+    fn crossed_artifacts_fit() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let inputs = arbitrary_inputs();
+        for (seed, inputs) in (0..).zip(inputs.windows(2)) {
+            let data1 = inputs[0].to_artifact().unwrap();
+            let data2 = inputs[1].to_artifact().unwrap();
+            let mut out = vec![0; rng.gen_range(0..2048)];
+            let size = crossover_inputs(&data1, &data2, &mut out, seed);
+            assert!(size <= out.len());
+            Input::from_artifact(&out[..size]).unwrap();
+        }
+    }
+
+    #[test]
+    fn any_bytes_decode() {
+        // So the fallbacks for undecodable artifacts are only defensive.
+        let mut rng = StdRng::seed_from_u64(3);
+        assert!(Input::from_artifact(&[]).is_ok());
+        for _ in 0..200 {
+            assert!(Input::from_artifact(&random_bytes(&mut rng)).is_ok());
+        }
+    }
+
+    #[test]
+    fn crossover_fails_if_the_input_cant_be_encoded() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let inputs = arbitrary_inputs();
+        for inputs in inputs.windows(2) {
+            let mut input1 = inputs[0].clone();
+            input1.address_generator.address_seed = u64::MAX;
+            assert!(input1.to_artifact().is_err());
+            assert_eq!(crossover(&mut rng, input1, &inputs[1], 4096), None);
+        }
+    }
+}
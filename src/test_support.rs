@@ -0,0 +1,238 @@
+//! Support code for the tests [`generate_test`](crate::generate_test) writes.
+//!
+//! A generated test recreates the fuzzer's ledger in a [`TestLedger`],
+//! makes the fuzzer's calls with [`call`] and [`forward`],
+//! and advances the ledger with the same code as the fuzzer,
+//! so that it only differs from the fuzzer in what it leaves out.
+
+pub use crate::accounts::{
+    Ed25519Account, MockAuthContract, MultisigAccount, PolicyAccount, ReentrantAccount,
+    ReentrantCall,
+};
+pub use crate::fuzz::ProxyContract;
+
+use crate::addrgen::create_classic_account;
+use crate::fuzz::{
+    advance_time, assert_auths, passphrase_network_id, verify_token_contract_result,
+};
+use crate::util::address_to_bytes;
+use soroban_sdk::testutils::ContractFunctionSet;
+use soroban_sdk::token::Client;
+use soroban_sdk::xdr::{Limits, ReadXdr, ScAddress, SorobanAuthorizationEntry};
+use soroban_sdk::{Address, Bytes, Env, Error, InvokeError, Symbol, Val};
+use std::rc::Rc;
+use std::vec::Vec as RustVec;
+
+/// The token, the proxy contract and the accounts,
+/// at the addresses they had in the fuzzer.
+///
+/// Advancing the ledger rebuilds `env` from a snapshot, as the fuzzer does,
+/// and with it the addresses, which belong to the old `env`.
+#[derive(Clone)]
+pub struct TestLedger {
+    pub env: Env,
+    pub token: Address,
+    pub proxy: Address,
+    pub accounts: RustVec<Address>,
+    /// Registers the token's contract again,
+    /// as `ContractTokenOps::reregister_contract` does.
+    reregister_token: fn(&Env, &Address),
+    /// The account contracts to register again,
+    /// by the index of their account.
+    contracts: RustVec<(usize, RegisterContract)>,
+}
+
+/// Registers a contract at an address in an `Env`.
+type RegisterContract = Rc<dyn Fn(&Env, &Address)>;
+
+impl TestLedger {
+    /// A ledger with nothing at the strkeys of the token,
+    /// the proxy contract and the accounts.
+    pub fn new(
+        token: &str,
+        proxy: &str,
+        accounts: &[&str],
+        reregister_token: fn(&Env, &Address),
+    ) -> TestLedger {
+        let env = Env::default();
+        env.budget().reset_unlimited();
+
+        let address = |strkey: &str| address_from_bytes(&env, strkey.as_bytes());
+        TestLedger {
+            token: address(token),
+            proxy: address(proxy),
+            accounts: accounts.iter().map(|account| address(account)).collect(),
+            env,
+            reregister_token,
+            contracts: RustVec::new(),
+        }
+    }
+
+    /// Create the classic account at `accounts[index]`,
+    /// with the strkeys and weights of its signers besides the master key.
+    pub fn create_account(
+        &self,
+        index: usize,
+        master_weight: u8,
+        medium_threshold: u8,
+        signers: &[(&str, u8)],
+    ) {
+        let ScAddress::Account(account_id) = ScAddress::try_from(&self.accounts[index]).unwrap()
+        else {
+            panic!("accounts[{index}] is a contract");
+        };
+        let signers: RustVec<_> = signers
+            .iter()
+            .map(|(strkey, weight)| (public_key(strkey), *weight))
+            .collect();
+        create_classic_account(
+            &self.env,
+            &account_id,
+            master_weight,
+            medium_threshold,
+            &signers,
+        );
+    }
+
+    /// Register the token with `accounts[0]` as its admin,
+    /// and then the proxy contract, as the fuzzer does.
+    ///
+    /// The authorization entries are signed for the token's address,
+    /// so `register_token` must register it there.
+    pub fn register_token(&self, register_token: fn(&Env, &Address) -> Address) {
+        let token = register_token(&self.env, &self.accounts[0]);
+        assert_eq!(
+            token, self.token,
+            "the authorization entries are signed for the token's address in the fuzzer",
+        );
+        self.env.register_contract(&self.proxy, ProxyContract);
+    }
+
+    /// Register the account contract of `accounts[index]`,
+    /// now and after each ledger advance.
+    pub fn register_contract(
+        &mut self,
+        index: usize,
+        contract: impl ContractFunctionSet + Clone + 'static,
+    ) {
+        let register: RegisterContract = Rc::new(move |env, address| {
+            env.register_contract(address, contract.clone());
+        });
+        register(&self.env, &self.accounts[index]);
+        self.contracts.push((index, register));
+    }
+
+    /// Advance the ledger as the fuzzer does between transactions,
+    /// moving to the network of `network_passphrase` if it is given.
+    pub fn advance_ledgers(&mut self, ledgers: u32, network_passphrase: Option<&str>) {
+        let token = address_to_bytes(&self.token);
+        let proxy = address_to_bytes(&self.proxy);
+        let accounts: RustVec<_> = self.accounts.iter().map(address_to_bytes).collect();
+
+        let env = std::mem::take(&mut self.env);
+        self.env = advance_time(
+            env,
+            &token,
+            ledgers,
+            network_passphrase.map(passphrase_network_id),
+            |env, token| {
+                (self.reregister_token)(env, token);
+                env.register_contract(&address_from_bytes(env, &proxy), ProxyContract);
+                for (index, register) in &self.contracts {
+                    register(env, &address_from_bytes(env, &accounts[*index]));
+                }
+            },
+        );
+        self.env.budget().reset_unlimited();
+
+        self.token = address_from_bytes(&self.env, &token);
+        self.proxy = address_from_bytes(&self.env, &proxy);
+        self.accounts = accounts
+            .iter()
+            .map(|account| address_from_bytes(&self.env, account))
+            .collect();
+    }
+
+    /// Assert the balance of each of the first `number_of_accounts` accounts,
+    /// and the allowance of each (from, spender) pair of them.
+    pub fn assert_state(&self, number_of_accounts: usize, balances: &[i128], allowances: &[i128]) {
+        let accounts = &self.accounts[..number_of_accounts];
+        let client = Client::new(&self.env, &self.token);
+        for (account, balance) in accounts.iter().zip(balances) {
+            assert_eq!(client.balance(account), *balance);
+        }
+        let pairs = accounts
+            .iter()
+            .flat_map(|from| accounts.iter().map(move |spender| (from, spender)));
+        for ((from, spender), allowance) in pairs.zip(allowances) {
+            assert_eq!(client.allowance(from, spender), *allowance);
+        }
+    }
+}
+
+/// Call the token with the authorization entries `auths`, in XDR.
+///
+/// If the call succeeds, it must have been authorized by `signer` alone.
+pub fn call(
+    env: &Env,
+    token: &Address,
+    signer: &Address,
+    fn_name: &str,
+    args: soroban_sdk::Vec<Val>,
+    auths: &[&str],
+) -> Result<(), Result<Error, InvokeError>> {
+    env.set_auths(&parse_auths(auths));
+    let r = env.try_invoke_contract::<(), Error>(token, &Symbol::new(env, fn_name), args.clone());
+
+    if r.is_ok() {
+        assert_auths(env, signer, token, fn_name, args, None);
+    }
+    verify_token_contract_result(env, &r);
+    r.map(|r| r.expect("no return value"))
+}
+
+/// Call the token through the proxy contract on behalf of `caller`,
+/// with the authorization entries `auths`, in XDR.
+///
+/// If the call succeeds, `caller` must have authorized
+/// the call to the proxy alone, with the token call under it.
+pub fn forward(
+    env: &Env,
+    proxy: &Address,
+    token: &Address,
+    caller: &Address,
+    fn_name: &str,
+    args: soroban_sdk::Vec<Val>,
+    auths: &[&str],
+) -> Result<(), Result<Error, InvokeError>> {
+    env.set_auths(&parse_auths(auths));
+    let r = env.try_invoke_contract::<(), Error>(
+        proxy,
+        &Symbol::new(env, "forward"),
+        ProxyContract::forward_args(env, caller, token, fn_name, args.clone()),
+    );
+
+    if r.is_ok() {
+        assert_auths(env, caller, token, fn_name, args, Some(proxy));
+    }
+    verify_token_contract_result(env, &r);
+    r.map(|r| r.expect("no return value"))
+}
+
+/// The ed25519 public key of an account's strkey.
+pub fn public_key(strkey: &str) -> [u8; 32] {
+    stellar_strkey::ed25519::PublicKey::from_string(strkey)
+        .unwrap()
+        .0
+}
+
+fn parse_auths(auths: &[&str]) -> RustVec<SorobanAuthorizationEntry> {
+    auths
+        .iter()
+        .map(|auth| SorobanAuthorizationEntry::from_xdr_base64(auth, Limits::none()).unwrap())
+        .collect()
+}
+
+fn address_from_bytes(env: &Env, strkey: &[u8]) -> Address {
+    Address::from_string_bytes(&Bytes::from_slice(env, strkey))
+}
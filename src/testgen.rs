@@ -0,0 +1,664 @@
+//! Generating standalone unit tests from failing inputs.
+//!
+//! The input is run with a [`Recording`] attached to the fuzzer,
+//! which writes each call to the token as a line of Rust,
+//! along with the ledger advances and the model's
+//! balances and allowances at the end of each transaction.
+//!
+//! The generated test depends on `soroban-sdk`
+//! and on this crate's [`test_support`](crate::test_support),
+//! so it can be pasted into the token's own crate
+//! with this crate as a dev-dependency.
+//! Each call is made with the exact authorization entries
+//! the fuzzer signed, corrupted or not,
+//! so the test has the fuzzer's accounts and contracts:
+//! classic accounts with their signers and thresholds,
+//! the account contracts and the proxy contract,
+//! and ledger advances that expire entries
+//! and move to other networks with the fuzzer's own code.
+//! It is still simpler than the fuzzer:
+//!
+//! - The token must be registered at the same address as in the fuzzer,
+//!   which the entries are signed for.
+//! - Extension commands aren't reproduced.
+
+use crate::accounts::{AccountContract, ClassicAccount, ReentrantCall};
+use crate::config::{Config, TokenContractResult};
+use crate::fuzz::{record_token, ExpectedError};
+use crate::input::Input;
+use crate::model::TokenModel;
+use crate::util::address_to_bytes;
+use crate::TestSigner;
+use ed25519_dalek::SigningKey;
+use itertools::Itertools;
+use soroban_sdk::xdr::{Limits, ScError, ScVal, SorobanAuthorizationEntry, WriteXdr};
+use soroban_sdk::{Address, Error, TryFromVal, Val};
+use std::any::Any;
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::string::String as RustString;
+use std::vec::Vec as RustVec;
+
+/// How the generated test registers the token.
+pub struct TestSetup {
+    /// The name of the test function.
+    pub name: RustString,
+    /// The body of `fn register_token(env: &Env, admin: &Address) -> Address`,
+    /// which registers and initializes the token,
+    /// returning its address.
+    pub register_token: RustString,
+    /// The body of `fn reregister_token(env: &Env, token: &Address)`,
+    /// which registers the token's contract again after a ledger advance,
+    /// as [`ContractTokenOps::reregister_contract`](crate::ContractTokenOps::reregister_contract) does.
+    /// Empty for tokens that don't need it.
+    pub reregister_token: RustString,
+}
+
+/// Generate a `#[test]` that makes the same calls as `input`,
+/// ending with the assertion the fuzzer failed.
+///
+/// If the input doesn't fail, the test asserts everything
+/// the fuzzer checked and passes.
+pub fn generate_test(config: Config, input: &Input, setup: &TestSetup) -> RustString {
+    let recording = Recording::default();
+
+    let r = panic::catch_unwind(AssertUnwindSafe(|| {
+        record_token(config, input, &recording);
+    }));
+    let failure = r.err().map(|payload| panic_message(&*payload));
+
+    let steps = recording.0.borrow();
+    steps.render(setup, failure.as_deref())
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> RustString {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<RustString>() {
+        msg.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Records what the fuzzer does as the lines of a test.
+#[derive(Clone, Default)]
+pub(crate) struct Recording(Rc<RefCell<Steps>>);
+
+/// A call the fuzzer made to the token,
+/// directly or through the proxy contract.
+pub(crate) struct RecordedCall<'a> {
+    pub(crate) fn_name: &'a str,
+    pub(crate) args: &'a soroban_sdk::Vec<Val>,
+    /// The account that must authorize the call,
+    /// which is also the caller of the proxy contract.
+    pub(crate) signer: &'a Address,
+    pub(crate) via_proxy: bool,
+    /// The authorization entries the call was made with.
+    pub(crate) auths: &'a [SorobanAuthorizationEntry],
+    pub(crate) result: &'a TokenContractResult,
+}
+
+#[derive(Default)]
+struct Steps {
+    /// The token's address in the fuzzer.
+    token: RustString,
+    /// The proxy contract's address in the fuzzer.
+    proxy: RustString,
+    /// The addresses of all the accounts seen so far.
+    accounts: RustVec<RustString>,
+    /// How to set up each of `accounts`.
+    account_setups: RustVec<AccountSetup>,
+    lines: RustVec<RustString>,
+}
+
+enum AccountSetup {
+    /// The arguments to `create_account` after the account.
+    Classic(RustString),
+    /// The account contract to register at the address.
+    Contract(RustString),
+}
+
+impl Recording {
+    /// Record the token, the proxy contract and the accounts the fuzzer set up.
+    pub(crate) fn setup(&self, token: &Address, proxy: &Address, accounts: &[TestSigner]) {
+        let mut steps = self.0.borrow_mut();
+        steps.token = strkey(token);
+        steps.proxy = strkey(proxy);
+        steps.update_accounts(accounts);
+    }
+
+    /// Record a call to the token,
+    /// with the errors the model predicts, as from `expected_error`.
+    pub(crate) fn call(
+        &self,
+        call: &RecordedCall,
+        expected: Option<ExpectedError>,
+        accounts: &[TestSigner],
+    ) {
+        let mut steps = self.0.borrow_mut();
+        steps.update_accounts(accounts);
+
+        let line = match (expected, call.result) {
+            (Some(ExpectedError::OneOf(errors)), _) if errors.len() == 1 => format!(
+                "assert_eq!(\n        {},\n        Err(Ok({})),\n    );",
+                steps.render_call(call, 8),
+                render_error(&errors[0])
+            ),
+            (Some(ExpectedError::OneOf(errors)), _) => format!(
+                "assert!(matches!(\n        {},\n        Err(Ok(e)) if [{}].contains(&e),\n    ));",
+                steps.render_call(call, 8),
+                errors.iter().map(render_error).join(", ")
+            ),
+            (Some(ExpectedError::Any), _) => {
+                format!("assert!({}\n    .is_err());", steps.render_call(call, 4))
+            }
+            (None, Ok(_)) => format!(
+                "assert_eq!(\n        {},\n        Ok(()),\n    );",
+                steps.render_call(call, 8)
+            ),
+            (None, Err(e)) => format!(
+                "// The fuzzer doesn't predict this failure: {e:?}\n    let _ = {};",
+                steps.render_call(call, 4)
+            ),
+        };
+        steps.lines.push(line);
+    }
+
+    /// Record the end of a transaction,
+    /// after which the token should match the model.
+    ///
+    /// `network_passphrase` is that of the network the ledger moved to, if any.
+    pub(crate) fn end_transaction(
+        &self,
+        advance_ledgers: u32,
+        network_passphrase: Option<&str>,
+        model: &dyn TokenModel,
+        accounts: &[TestSigner],
+    ) {
+        let mut steps = self.0.borrow_mut();
+        steps.update_accounts(accounts);
+
+        let network_passphrase = match network_passphrase {
+            Some(network_passphrase) => format!("Some({network_passphrase:?})"),
+            None => "None".to_string(),
+        };
+        steps.lines.push(format!(
+            "test.advance_ledgers({advance_ledgers}, {network_passphrase});"
+        ));
+
+        let balances = accounts
+            .iter()
+            .map(|signer| model.balance(&signer.address).to_string())
+            .collect::<RustVec<_>>()
+            .join(", ");
+        let allowances = accounts
+            .iter()
+            .flat_map(|signer1| {
+                accounts
+                    .iter()
+                    .map(|signer2| model.allowance(&signer1.address, &signer2.address))
+            })
+            .map(|allowance| allowance.to_string())
+            .collect::<RustVec<_>>()
+            .join(", ");
+        steps.lines.push(format!(
+            "test.assert_state(\n        {},\n        &[{balances}],\n        &[{allowances}],\n    );",
+            accounts.len()
+        ));
+    }
+
+    /// Record a step the test doesn't reproduce.
+    pub(crate) fn comment(&self, comment: &str) {
+        self.0.borrow_mut().lines.push(format!("// {comment}"));
+    }
+}
+
+impl Steps {
+    fn update_accounts(&mut self, accounts: &[TestSigner]) {
+        for signer in accounts.iter().skip(self.accounts.len()) {
+            let setup = match (&signer.classic_account, &signer.account_contract) {
+                (Some(classic_account), _) => {
+                    AccountSetup::Classic(render_classic_account(classic_account))
+                }
+                (None, Some(account_contract)) => {
+                    AccountSetup::Contract(render_account_contract(account_contract))
+                }
+                (None, None) => unreachable!("every account is classic or a contract"),
+            };
+            self.accounts.push(strkey(&signer.address));
+            self.account_setups.push(setup);
+        }
+    }
+
+    fn render_address(&self, address: &str) -> RustString {
+        if address == self.token {
+            return "&token".to_string();
+        }
+        if address == self.proxy {
+            return "&proxy".to_string();
+        }
+        match self.accounts.iter().position(|account| account == address) {
+            Some(index) => format!("&accounts[{index}]"),
+            None => format!("&Address::from_string(&String::from_str(&env, \"{address}\"))"),
+        }
+    }
+
+    /// Render a call to the `call` or `forward` helper,
+    /// with its closing parenthesis indented by `indent`.
+    fn render_call(&self, call: &RecordedCall, indent: usize) -> RustString {
+        let arg_indent = " ".repeat(indent + 4);
+
+        let mut args = vec!["&env".to_string()];
+        if call.via_proxy {
+            args.push("&proxy".to_string());
+        }
+        args.push("&token".to_string());
+        args.push(self.render_address(&strkey(call.signer)));
+        args.push(format!("{:?}", call.fn_name));
+        args.push(self.render_args(call.args));
+        args.push(match call.auths {
+            [] => "&[]".to_string(),
+            auths => {
+                let entries: RustString = auths
+                    .iter()
+                    .map(|entry| {
+                        let entry = entry.to_xdr_base64(Limits::none()).unwrap();
+                        format!("{arg_indent}    {entry:?},\n")
+                    })
+                    .collect();
+                format!("&[\n{entries}{arg_indent}]")
+            }
+        });
+
+        let helper = if call.via_proxy { "forward" } else { "call" };
+        let args: RustString = args
+            .iter()
+            .map(|arg| format!("{arg_indent}{arg},\n"))
+            .collect();
+        format!("{helper}(\n{args}{})", " ".repeat(indent))
+    }
+
+    fn render_args(&self, args: &soroban_sdk::Vec<Val>) -> RustString {
+        let env = args.env();
+        let args: RustVec<RustString> = args
+            .iter()
+            .map(|arg| self.render_arg(&ScVal::try_from_val(env, &arg).unwrap()))
+            .collect();
+        match args.as_slice() {
+            [] => "Vec::new(&env)".to_string(),
+            [arg] => format!("({arg},).into_val(&env)"),
+            args => format!("({}).into_val(&env)", args.join(", ")),
+        }
+    }
+
+    fn render_arg(&self, arg: &ScVal) -> RustString {
+        match arg {
+            ScVal::Address(address) => self.render_address(&address.to_string()),
+            ScVal::Bool(b) => b.to_string(),
+            ScVal::U32(n) => format!("{n}_u32"),
+            ScVal::I32(n) => format!("{n}_i32"),
+            ScVal::U64(n) => format!("{n}_u64"),
+            ScVal::I64(n) => format!("{n}_i64"),
+            ScVal::I128(n) => render_i128(n.into()),
+            ScVal::U128(n) => format!("{}_u128", u128::from(n)),
+            ScVal::Symbol(s) => format!("Symbol::new(&env, {:?})", s.to_utf8_string_lossy()),
+            ScVal::String(s) => format!("String::from_str(&env, {:?})", s.to_utf8_string_lossy()),
+            arg => format!(
+                "Val::try_from_val(&env, &ScVal::from_xdr_base64({:?}, Limits::none()).unwrap()).unwrap()",
+                arg.to_xdr_base64(Limits::none()).unwrap()
+            ),
+        }
+    }
+
+    fn render(&self, setup: &TestSetup, failure: Option<&str>) -> RustString {
+        let mut test = RustString::new();
+
+        test.push_str("#[test]\n");
+        test.push_str(&format!("fn {}() {{\n", setup.name));
+        match failure {
+            Some(failure) => {
+                test.push_str("    // Generated from an input the fuzzer failed with:\n");
+                for line in failure.lines() {
+                    test.push_str(&format!("    // {line}\n"));
+                }
+            }
+            None => test.push_str("    // Generated from an input the fuzzer passed.\n"),
+        }
+        test.push('\n');
+
+        test.push_str(PRELUDE);
+
+        push_fn(
+            &mut test,
+            "fn register_token(env: &Env, admin: &Address) -> Address",
+            &setup.register_token,
+        );
+        test.push_str("    #[allow(unused_variables)]\n");
+        push_fn(
+            &mut test,
+            "fn reregister_token(env: &Env, token: &Address)",
+            &setup.reregister_token,
+        );
+
+        let advances = |line: &RustString| line.starts_with("test.advance_ledgers(");
+        let has_contracts = self
+            .account_setups
+            .iter()
+            .any(|setup| matches!(setup, AccountSetup::Contract(_)));
+        let mutable = has_contracts || self.lines.iter().any(advances);
+
+        let binding = if mutable { "let mut test" } else { "let test" };
+        test.push_str(&format!("    {binding} = TestLedger::new(\n"));
+        test.push_str(&format!("        \"{}\",\n", self.token));
+        test.push_str(&format!("        \"{}\",\n", self.proxy));
+        test.push_str("        &[\n");
+        for account in &self.accounts {
+            test.push_str(&format!("            \"{account}\",\n"));
+        }
+        test.push_str("        ],\n");
+        test.push_str("        reregister_token,\n");
+        test.push_str("    );\n");
+        for (index, setup) in self.account_setups.iter().enumerate() {
+            if let AccountSetup::Classic(args) = setup {
+                test.push_str(&format!("    test.create_account({index}, {args});\n"));
+            }
+        }
+        test.push_str("    test.register_token(register_token);\n");
+        for (index, setup) in self.account_setups.iter().enumerate() {
+            if let AccountSetup::Contract(contract) = setup {
+                test.push_str(&format!(
+                    "    test.register_contract({index}, {contract});\n"
+                ));
+            }
+        }
+
+        // The calls use the addresses in the current `env`,
+        // which each ledger advance replaces.
+        let mut stale = true;
+        for (i, line) in self.lines.iter().enumerate() {
+            let makes_call = line.contains("call(\n") || line.contains("forward(\n");
+            if stale && makes_call {
+                let forwards = self.lines[i..]
+                    .iter()
+                    .take_while(|line| !advances(line))
+                    .any(|line| line.contains("forward(\n"));
+                let proxy = if forwards { " proxy," } else { "" };
+                test.push_str(&format!(
+                    "\n    let TestLedger {{ env, token,{proxy} accounts, .. }} = test.clone();\n"
+                ));
+                stale = false;
+            } else if !line.starts_with("test.assert_state(") {
+                // Each transaction's assertions follow its ledger advance.
+                test.push('\n');
+            }
+            test.push_str(&format!("    {line}\n"));
+            if advances(line) {
+                stale = true;
+            }
+        }
+
+        test.push_str("}\n");
+        test
+    }
+}
+
+/// Push a function with the lines of `body`, indented.
+fn push_fn(test: &mut RustString, signature: &str, body: &str) {
+    let body = body.trim();
+    if body.is_empty() {
+        test.push_str(&format!("    {signature} {{}}\n\n"));
+        return;
+    }
+    test.push_str(&format!("    {signature} {{\n"));
+    for line in body.lines() {
+        test.push_str(&format!("        {line}\n"));
+    }
+    test.push_str("    }\n\n");
+}
+
+fn render_classic_account(classic_account: &ClassicAccount) -> RustString {
+    let (master, signers) = classic_account.signers.split_first().unwrap();
+    // Zero-weight signers aren't stored, as in the fuzzer.
+    let signers = signers
+        .iter()
+        .filter(|signer| signer.weight > 0)
+        .map(|signer| format!("({:?}, {})", public_key_strkey(&signer.key), signer.weight))
+        .join(", ");
+    format!(
+        "{}, {}, &[{signers}]",
+        master.weight, classic_account.medium_threshold
+    )
+}
+
+fn render_account_contract(account_contract: &AccountContract) -> RustString {
+    match account_contract {
+        AccountContract::AcceptAll => "MockAuthContract".to_string(),
+        AccountContract::Ed25519(key) => format!(
+            "Ed25519Account {{ public_key: public_key({:?}) }}",
+            public_key_strkey(key)
+        ),
+        AccountContract::Multisig { keys, .. } => format!(
+            "MultisigAccount {{ public_keys: [{}].map(public_key) }}",
+            keys.iter()
+                .map(|key| format!("{:?}", public_key_strkey(key)))
+                .join(", ")
+        ),
+        AccountContract::Policy {
+            denied_fn_name,
+            amount_limit,
+        } => format!(
+            "PolicyAccount {{ denied_fn_name: {denied_fn_name:?}, amount_limit: {} }}",
+            render_i128(*amount_limit)
+        ),
+        AccountContract::Reentrant {
+            call,
+            propagate_error,
+        } => {
+            let call = match call {
+                ReentrantCall::Transfer(amount) => format!("Transfer({})", render_i128(*amount)),
+                ReentrantCall::Approve(amount) => format!("Approve({})", render_i128(*amount)),
+                ReentrantCall::Balance => "Balance".to_string(),
+            };
+            format!(
+                "ReentrantAccount {{ call: ReentrantCall::{call}, propagate_error: {propagate_error} }}"
+            )
+        }
+    }
+}
+
+fn render_i128(n: i128) -> RustString {
+    match n {
+        i128::MAX => "i128::MAX".to_string(),
+        i128::MIN => "i128::MIN".to_string(),
+        n => format!("{n}_i128"),
+    }
+}
+
+const PRELUDE: &str = "    \
+    #[allow(unused_imports)]
+    use soroban_sdk::xdr::{Limits, ReadXdr, ScErrorCode, ScErrorType, ScVal};
+    #[allow(unused_imports)]
+    use soroban_sdk::{Address, Env, Error, IntoVal, String, Symbol, TryFromVal, Val, Vec};
+    use soroban_token_fuzzer::test_support::*;
+
+";
+
+fn strkey(address: &Address) -> RustString {
+    RustString::from_utf8(address_to_bytes(address)).unwrap()
+}
+
+fn public_key_strkey(key: &SigningKey) -> RustString {
+    stellar_strkey::ed25519::PublicKey(key.verifying_key().to_bytes()).to_string()
+}
+
+fn render_error(error: &Error) -> RustString {
+    let sc_error = ScError::try_from(*error).unwrap();
+    let code = match sc_error {
+        ScError::Contract(code) => return format!("Error::from_contract_error({code})"),
+        ScError::WasmVm(code)
+        | ScError::Context(code)
+        | ScError::Storage(code)
+        | ScError::Object(code)
+        | ScError::Crypto(code)
+        | ScError::Events(code)
+        | ScError::Budget(code)
+        | ScError::Value(code)
+        | ScError::Auth(code) => code,
+    };
+    format!(
+        "Error::from_type_and_code(ScErrorType::{}, ScErrorCode::{})",
+        sc_error.discriminant().name(),
+        code.name()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ErrorCodes;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::path::Path;
+    use std::process::Command;
+
+    fn scratch_manifest(manifest_dir: &Path) -> RustString {
+        format!(
+            r#"[package]
+name = "generated-tests"
+version = "0.0.0"
+edition = "2021"
+publish = false
+
+[dependencies]
+soroban-sdk = {{ version = "20.1.0", features = ["testutils"] }}
+soroban-token-fuzzer = {{ path = {:?} }}
+
+[workspace]
+"#,
+            manifest_dir.display().to_string()
+        )
+    }
+
+    /// Generate tests from random inputs with a few commands,
+    /// against the native token declaring its own errors
+    /// or declaring the wrong error for negative amounts.
+    fn generated_tests() -> RustVec<RustString> {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut tests = RustVec::new();
+        while tests.len() < 24 {
+            let mut bytes = vec![0; rng.gen_range(0..2048)];
+            rng.fill(&mut bytes[..]);
+            let Ok(input) = Input::from_artifact(&bytes) else {
+                continue;
+            };
+            let commands: usize = input.transactions.iter().map(|tx| tx.commands.len()).sum();
+            if commands < 4 {
+                continue;
+            }
+
+            let (name, config) = match tests.len() % 3 {
+                0 => (
+                    "wrong_error",
+                    Config::native().with_error_codes(ErrorCodes {
+                        negative_amount: vec![Error::from_contract_error(99)],
+                        ..ErrorCodes::stellar_asset()
+                    }),
+                ),
+                _ => ("native", Config::native()),
+            };
+            let setup = TestSetup {
+                name: format!("{name}_{}", tests.len()),
+                register_token: "env.register_stellar_asset_contract(admin.clone())".to_string(),
+                reregister_token: RustString::new(),
+            };
+            tests.push(generate_test(config, &input, &setup));
+        }
+        tests
+    }
+
+    /// Whether the fuzzer passed the input a generated test is from.
+    fn passed(test: &str) -> bool {
+        test.contains("// Generated from an input the fuzzer passed.")
+    }
+
+    #[test]
+    fn generated_tests_cover_the_fuzzer() {
+        let tests = generated_tests();
+        for feature in [
+            "forward(\n",
+            "test.create_account(0, ",
+            "&[(\"G",
+            "test.register_contract(",
+            "Some(\"Soroban Token Fuzzer Network",
+        ] {
+            assert!(
+                tests.iter().any(|test| test.contains(feature)),
+                "no generated test has {feature:?}"
+            );
+        }
+        assert!(tests.iter().any(|test| !passed(test)));
+
+        // Each call is made in the `env` of the latest ledger advance.
+        for test in &tests {
+            let mut current = false;
+            for line in test.lines() {
+                if line.contains("let TestLedger {") {
+                    current = true;
+                } else if line.contains("test.advance_ledgers(") {
+                    current = false;
+                } else if line.contains("&env") {
+                    assert!(current, "`{line}` uses an old `env`:\n{test}");
+                }
+            }
+        }
+    }
+
+    /// Build and run the generated tests in a scratch crate.
+    ///
+    /// This builds this crate again, so it's left to `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn generated_tests_reproduce_the_fuzzer() {
+        let tests = generated_tests();
+
+        // The crate is built against the same dependencies as this one.
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let scratch = manifest_dir.join("target").join("testgen");
+        std::fs::create_dir_all(scratch.join("src")).unwrap();
+        std::fs::write(scratch.join("Cargo.toml"), scratch_manifest(manifest_dir)).unwrap();
+        std::fs::copy(manifest_dir.join("Cargo.lock"), scratch.join("Cargo.lock")).unwrap();
+        std::fs::write(
+            scratch.join("src").join("lib.rs"),
+            format!("#![cfg(test)]\n\n{}", tests.join("\n")),
+        )
+        .unwrap();
+
+        let output = Command::new(env!("CARGO"))
+            .args(["test", "--offline", "--", "--test-threads=1"])
+            .current_dir(&scratch)
+            .env("CARGO_TARGET_DIR", scratch.join("target"))
+            .output()
+            .unwrap();
+        let stdout = RustString::from_utf8_lossy(&output.stdout);
+        let stderr = RustString::from_utf8_lossy(&output.stderr);
+        assert!(
+            stdout.contains("test result:"),
+            "the generated tests don't build:\n{stderr}"
+        );
+
+        for test in &tests {
+            let name = test
+                .strip_prefix("#[test]\nfn ")
+                .and_then(|test| test.split_once("()"))
+                .unwrap()
+                .0;
+            let result = if passed(test) { "ok" } else { "FAILED" };
+            assert!(
+                stdout.contains(&format!("test {name} ... {result}\n")),
+                "`{name}` should be {result}:\n{stdout}\n{test}"
+            );
+        }
+    }
+}
@@ -14,3 +14,15 @@ pub fn address_to_bytes(addr: &Address) -> RustVec<u8> {
     addr_str.copy_into_slice(&mut buf);
     buf
 }
+
+/// The functions of the token interface, plus `mint`.
+pub const TOKEN_FN_NAMES: &[&str] = &[
+    "allowance",
+    "approve",
+    "balance",
+    "transfer",
+    "transfer_from",
+    "burn",
+    "burn_from",
+    "mint",
+];